@@ -0,0 +1,515 @@
+//! Requests to the heroku Platform API and parsing of its responses
+//!
+//! Both the blocking and the async clients of the Platform API build their requests and read
+//! the responses with these functions, they only differ by the way the request is sent.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let request = api::get_config_vars("my-heroku-api-token", "my-app");
+//! let response = send(request);
+//! let config_vars = api::config_vars(&response)?;
+//! ```
+use heroku::{AddonAttachment, PlatformError, Release};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, RANGE};
+use reqwest::{Method, StatusCode};
+use serde_json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// URL of the heroku Platform API, the paths of the requests are relative to it
+pub static BASE_URL: &str = "https://api.heroku.com";
+
+/// A request to the Platform API, independent of the client sending it
+pub struct Request {
+    /// The HTTP method
+    pub method: Method,
+    /// The path of the endpoint (ex: /apps/my-app/config-vars)
+    pub path: String,
+    /// The headers, with the API token
+    pub headers: HeaderMap,
+    /// The JSON body, if any
+    pub body: Option<String>,
+}
+
+/// A response of the Platform API, once its body is read
+pub struct Response {
+    /// The HTTP status
+    pub status: StatusCode,
+    /// The `Next-Range` header, requesting the next page of a list
+    pub next_range: Option<String>,
+    /// The body of the response in raw text
+    pub body: String,
+}
+
+/// A list endpoint of the Platform API, fetched page by page
+pub struct List {
+    /// The path of the endpoint (ex: /apps)
+    pub path: String,
+    /// The `Range` header of the first page (ex: "name ..; max=1000")
+    pub range: &'static str,
+}
+
+impl List {
+    /// Every app the user has access to
+    pub fn apps() -> List {
+        List {
+            path: "/apps".to_string(),
+            range: "name ..; max=1000",
+        }
+    }
+
+    /// Every app owned by a team (or organization)
+    pub fn team_apps(team_name: &str) -> List {
+        List {
+            path: format!("/teams/{}/apps", team_name),
+            range: "name ..; max=1000",
+        }
+    }
+
+    /// Every release of an app, from the latest to the oldest
+    pub fn releases(app_name: &str) -> List {
+        List {
+            path: format!("/apps/{}/releases", app_name),
+            range: "version ..; order=desc, max=1000",
+        }
+    }
+
+    /// The latest release of an app, as the first page of a single release
+    pub fn latest_release(app_name: &str) -> List {
+        List {
+            path: format!("/apps/{}/releases", app_name),
+            range: "version ..; order=desc, max=1",
+        }
+    }
+
+    /// The latest releases of an app, enough to find a release among concurrent ones
+    pub fn latest_releases(app_name: &str) -> List {
+        List {
+            path: format!("/apps/{}/releases", app_name),
+            range: "version ..; order=desc, max=10",
+        }
+    }
+
+    /// Every add-on attachment of an app
+    pub fn addon_attachments(app_name: &str) -> List {
+        List {
+            path: format!("/apps/{}/addon-attachments", app_name),
+            range: "name ..; max=1000",
+        }
+    }
+}
+
+/// Request setting and removing config vars of an app in a single update
+///
+/// # Arguments
+///
+/// * `token` - The heroku API token
+/// * `app_name` - The name of the app
+/// * `changes` - The new values of the config vars, `None` removes a config var
+///
+pub fn patch_config_vars(
+    token: &str,
+    app_name: &str,
+    changes: &BTreeMap<String, Option<String>>,
+) -> Request {
+    Request {
+        method: Method::PATCH,
+        path: format!("/apps/{}/config-vars", app_name),
+        headers: construct_headers(token),
+        body: Some(serde_json::to_string(changes).unwrap()),
+    }
+}
+
+/// Request getting the config vars of an app
+///
+/// # Arguments
+///
+/// * `token` - The heroku API token
+/// * `app_name` - The name of the app
+///
+pub fn get_config_vars(token: &str, app_name: &str) -> Request {
+    get(token, format!("/apps/{}/config-vars", app_name))
+}
+
+/// Request getting a release of an app
+///
+/// # Arguments
+///
+/// * `token` - The heroku API token
+/// * `app_name` - The name of the app
+/// * `release` - The id or the version of the release
+///
+pub fn get_release(token: &str, app_name: &str, release: &str) -> Request {
+    get(token, format!("/apps/{}/releases/{}", app_name, release))
+}
+
+/// Request getting a single page of a list endpoint
+///
+/// # Arguments
+///
+/// * `token` - The heroku API token
+/// * `path` - The path of the list endpoint
+/// * `range` - The `Range` header of the page (ex: "version ..; order=desc, max=1")
+///
+pub fn get_page(token: &str, path: &str, range: &str) -> Request {
+    let mut request = get(token, path.to_string());
+    request
+        .headers
+        .insert(RANGE, HeaderValue::from_str(range).unwrap());
+    request
+}
+
+/// Request getting a resource, without body
+fn get(token: &str, path: String) -> Request {
+    Request {
+        method: Method::GET,
+        path,
+        headers: construct_headers(token),
+        body: None,
+    }
+}
+
+/// Construct the necessary headers for HTTP request to heroku platform API
+///
+/// # Arguments
+///
+/// * `token` - The heroku API token
+///
+/// # Result
+///
+/// * `HeaderMap` - A Headers Map containing HTTP headers (see reqwest documentation)
+///
+pub fn construct_headers(token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.heroku+json; version=3"),
+    );
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    let access_token = format!("Bearer {}", token);
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&access_token).unwrap());
+    headers
+}
+
+/// Read the `Next-Range` header of a response
+///
+/// # Arguments
+///
+/// * `headers` - The headers of the response
+///
+pub fn next_range(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("Next-Range")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// Read the config vars of an app
+///
+/// # Result
+///
+/// * `Result<Vec<String>, PlatformError>` - Vector with strings (key=value), or PlatformError struct
+///
+pub fn config_vars(response: &Response) -> Result<Vec<String>, PlatformError> {
+    if !response.status.is_success() {
+        return Err(error_from_response(&response.body));
+    }
+    Ok(success_from_response(&response.body))
+}
+
+/// Read a release of an app
+///
+/// # Result
+///
+/// * `Result<Release, PlatformError>` - The release, or PlatformError struct
+///
+pub fn release(response: &Response) -> Result<Release, PlatformError> {
+    if !response.status.is_success() {
+        return Err(error_from_response(&response.body));
+    }
+    let serde_value: Value = serde_json::from_str(&response.body).unwrap();
+    Ok(release_from_value(serde_value))
+}
+
+/// Read a page of a list endpoint
+///
+/// The platform API returns lists in pages, the next page being requested by sending back
+/// the `Next-Range` header of a `206 Partial Content` response.
+///
+/// # Result
+///
+/// * `Result<(Vec<Value>, Option<String>), PlatformError>` - The records of the page and
+///   the range of the next page if any, or PlatformError struct
+///
+pub fn page(response: &Response) -> Result<(Vec<Value>, Option<String>), PlatformError> {
+    if !response.status.is_success() {
+        return Err(error_from_response(&response.body));
+    }
+
+    let mut next_range = None;
+    if response.status == StatusCode::PARTIAL_CONTENT {
+        next_range = response.next_range.clone();
+    }
+
+    let serde_value: Value = serde_json::from_str(&response.body).unwrap();
+    match serde_value {
+        Value::Array(page) => Ok((page, next_range)),
+        _ => Ok((Vec::new(), None)),
+    }
+}
+
+/// Select the latest release of an app, the first record of its releases
+///
+/// # Arguments
+///
+/// * `app_name` - The name of the app
+/// * `releases` - The releases of the app, from the latest to the oldest
+///
+pub fn latest_release(app_name: &str, releases: Vec<Value>) -> Result<Release, PlatformError> {
+    match releases.into_iter().next() {
+        Some(release) => Ok(release_from_value(release)),
+        None => Err(PlatformError {
+            id: "not_found".to_string(),
+            message: format!("No release was found for app {}.", app_name),
+        }),
+    }
+}
+
+/// Map a list of releases returned by the heroku API into Release structs
+pub fn releases_from_list(records: Vec<Value>) -> Vec<Release> {
+    records.into_iter().map(release_from_value).collect()
+}
+
+/// Map a list of add-on attachments returned by the heroku API into AddonAttachment structs
+///
+/// An attachment that can not be read is an error rather than being left out, the config vars
+/// it manages would not be protected otherwise.
+///
+/// # Arguments
+///
+/// * `records` - The add-on attachments as JSON objects
+///
+/// # Result
+///
+/// * `Result<Vec<AddonAttachment>, PlatformError>` - The attachments, or PlatformError struct
+///
+pub fn attachments_from_list(records: Vec<Value>) -> Result<Vec<AddonAttachment>, PlatformError> {
+    records
+        .into_iter()
+        .map(|attachment| {
+            serde_json::from_value(attachment).map_err(|err| PlatformError {
+                id: "invalid_response".to_string(),
+                message: format!("Could not read an add-on attachment: {}", err),
+            })
+        })
+        .collect()
+}
+
+/// Map a release returned by the heroku API into a Release struct
+///
+/// # Arguments
+///
+/// * `value` - A release as a JSON object
+///
+/// # Result
+///
+/// * `Release` - A struct containing the version, description and status of the release
+///
+fn release_from_value(value: Value) -> Release {
+    serde_json::from_value(value).unwrap()
+}
+
+/// Extract the `name` attribute of a list of records returned by the heroku API
+///
+/// # Arguments
+///
+/// * `records` - A list of JSON objects
+///
+/// # Result
+///
+/// * `Vec<String>` - The names of the records
+///
+pub fn names_from_list(records: &[Value]) -> Vec<String> {
+    records
+        .iter()
+        .filter_map(|record| record["name"].as_str())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Map a successful response from the heroku API to a Vector
+///
+/// # Arguments
+///
+/// * `response` - JSON as text response from an API call
+///
+/// # Result
+///
+/// * `Vec<String>` - A vector containing strings, formated as "key=value"
+///
+fn success_from_response(response: &str) -> Vec<String> {
+    // Parse the string response to JSON
+    let serde_value: Value = serde_json::from_str(&response).unwrap();
+
+    // Use this JSON as object to iterate on it
+    let config_vars = serde_value.as_object().unwrap();
+
+    let mut records = Vec::new();
+
+    // Sort by key rather than by "key=value", where '=' would come after some characters of keys
+    let mut keys: Vec<&String> = config_vars.keys().collect();
+    keys.sort();
+
+    // Iterate over the config vars and put the result in a Vector
+    for key in keys {
+        let result: String = format!(
+            "{}={}",
+            key.to_string(),
+            config_vars.get(key).unwrap().as_str().unwrap()
+        );
+        records.push(result);
+    }
+    records
+}
+
+/// Map an error from an HTTP call into a PlatformError struct
+///
+/// # Argruments
+///
+/// * `response` - JSON as text response from an API call
+///
+/// # Result
+///
+/// * `PlatformError` - A struct containing the id of the error message, and the text message
+///
+fn error_from_response(response: &str) -> PlatformError {
+    // Parse the string response to JSON and Deserialize in a PlatformError struct
+    let platform_error: PlatformError = serde_json::from_str(&response).unwrap();
+    platform_error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_headers() {
+        let headers = construct_headers("1234");
+
+        assert!(headers.contains_key(ACCEPT));
+        assert!(headers.contains_key(AUTHORIZATION));
+        assert!(headers.contains_key(CONTENT_TYPE));
+
+        assert_eq!(headers[ACCEPT], "application/vnd.heroku+json; version=3");
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[AUTHORIZATION], "Bearer 1234");
+    }
+
+    #[test]
+    fn should_build_requests() {
+        let mut changes = BTreeMap::new();
+        changes.insert("NEW".to_string(), Some("1".to_string()));
+        changes.insert("OLD".to_string(), None);
+        let request = patch_config_vars("1234", "fuzzy-app", &changes);
+        assert_eq!(request.method, Method::PATCH);
+        assert_eq!(request.path, "/apps/fuzzy-app/config-vars");
+        assert_eq!(request.body.unwrap(), "{\"NEW\":\"1\",\"OLD\":null}");
+
+        let list = List::latest_release("fuzzy-app");
+        let request = get_page("1234", &list.path, list.range);
+        assert_eq!(request.method, Method::GET);
+        assert_eq!(request.headers[RANGE], "version ..; order=desc, max=1");
+        assert_eq!(request.headers[AUTHORIZATION], "Bearer 1234");
+        assert!(request.body.is_none());
+    }
+
+    #[test]
+    fn should_map_config_vars_as_vec() {
+        let http_response = "{ \"TEST\": \"VALUE\" }".to_string();
+        let v: Vec<String> = success_from_response(&http_response);
+        assert_eq!(v[0], "TEST=VALUE");
+    }
+
+    #[test]
+    fn should_map_config_vars_in_order() {
+        let http_response = "{ \"ZOO\": \"1\", \"BAR\": \"2\", \"FOO\": \"3\" }".to_string();
+        let v: Vec<String> = success_from_response(&http_response);
+        assert_eq!(v, vec!["BAR=2", "FOO=3", "ZOO=1"]);
+
+        let http_response = "{ \"A0\": \"1\", \"A\": \"2\", \"A_B\": \"3\" }".to_string();
+        let v: Vec<String> = success_from_response(&http_response);
+        assert_eq!(v, vec!["A=2", "A0=1", "A_B=3"]);
+    }
+
+    #[test]
+    fn should_map_app_names() {
+        let http_response =
+            "[{ \"name\": \"app-1\" }, { \"id\": \"42\" }, { \"name\": \"app-2\" }]";
+        let records: Vec<Value> = serde_json::from_str(http_response).unwrap();
+        let names = names_from_list(&records);
+        assert_eq!(names, vec!["app-1", "app-2"]);
+    }
+
+    #[test]
+    fn should_read_the_next_page() {
+        let response = Response {
+            status: StatusCode::PARTIAL_CONTENT,
+            next_range: Some("name ]app-1..; max=1000".to_string()),
+            body: "[{ \"name\": \"app-1\" }]".to_string(),
+        };
+        let (records, next) = page(&response).unwrap();
+        assert_eq!(names_from_list(&records), vec!["app-1"]);
+        assert_eq!(next.unwrap(), "name ]app-1..; max=1000");
+
+        let response = Response {
+            status: StatusCode::OK,
+            next_range: Some("name ]app-1..; max=1000".to_string()),
+            body: "[]".to_string(),
+        };
+        assert!(page(&response).unwrap().1.is_none());
+    }
+
+    #[test]
+    fn should_map_addon_attachments() {
+        let http_response =
+            "[{ \"name\": \"DATABASE\", \"addon\": { \"name\": \"postgresql-curly-12345\" } }]";
+        let records: Vec<Value> = serde_json::from_str(http_response).unwrap();
+        let attachments = attachments_from_list(records).unwrap();
+        assert_eq!(attachments[0].name, "DATABASE");
+        assert_eq!(attachments[0].addon.name, "postgresql-curly-12345");
+
+        let http_response = "[{ \"name\": \"DATABASE\", \"addon\": { \"name\": \"postgresql-curly-12345\" } }, { \"name\": \"REDIS\" }]";
+        let records: Vec<Value> = serde_json::from_str(http_response).unwrap();
+        let err = attachments_from_list(records).unwrap_err();
+        assert_eq!(err.id, "invalid_response");
+        assert!(err.message.contains("missing field `addon`"));
+    }
+
+    #[test]
+    fn should_map_release() {
+        let http_response = "{ \"id\": \"abc\", \"version\": 42, \"description\": \"Set TEST config vars\", \"status\": \"pending\", \"created_at\": \"2019-09-01T12:00:00Z\", \"user\": { \"email\": \"john@example.com\" } }";
+        let value: Value = serde_json::from_str(http_response).unwrap();
+        let release = release_from_value(value);
+        assert_eq!(release.version, 42);
+        assert_eq!(release.description, "Set TEST config vars");
+        assert!(release.is_pending());
+        assert!(!release.is_failed());
+        assert_eq!(release.user.unwrap().email, "john@example.com");
+    }
+
+    #[test]
+    fn should_map_error_response() {
+        let http_response = "{ \"id\": \"Bad\", \"message\": \"This is bad\" }".to_string();
+        let err: PlatformError = error_from_response(&http_response);
+        assert_eq!(err.id, "Bad");
+        assert_eq!(err.message, "This is bad");
+
+        let response = Response {
+            status: StatusCode::NOT_FOUND,
+            next_range: None,
+            body: http_response,
+        };
+        assert_eq!(config_vars(&response).unwrap_err().id, "Bad");
+    }
+}
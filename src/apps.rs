@@ -0,0 +1,170 @@
+//! Resolve the apps given on the command line into concrete Heroku app names
+//!
+//! An app selector is either the exact name of an app, or a glob pattern such as `billing-*`
+//! or `api-*-staging`, that is matched against the list of apps available on the platform.
+//!
+//! # Example
+//!
+//! ```rust
+//! let available = vec![String::from("billing-eu"), String::from("billing-us")];
+//! let selectors = vec![String::from("billing-*")];
+//! let apps = apps::resolve(&selectors, &available);
+//! assert_eq!(apps.unwrap(), vec!["billing-eu", "billing-us"]);
+//! ```
+use glob::Pattern;
+
+/// Check if an app selector is a glob pattern rather than an app name
+///
+/// # Arguments
+/// * `selector` - An app name or a glob pattern
+///
+/// # Result
+/// * `bool` - true if the selector contains glob special characters
+///
+pub fn is_pattern(selector: &str) -> bool {
+    selector.contains(&['*', '?', '['][..])
+}
+
+/// Resolve a list of app selectors into app names
+///
+/// App names are kept as they are, glob patterns are expanded to every matching app of the
+/// available apps. The resulting list keeps the order of the selectors, without duplicates.
+///
+/// # Arguments
+/// * `selectors` - A list of app names or glob patterns
+/// * `available` - The names of the apps available on the platform
+///
+/// # Result
+/// * `Result<Vec<String>, String>` - The list of app names or an Error message
+///
+pub fn resolve(selectors: &[String], available: &[String]) -> Result<Vec<String>, String> {
+    let mut apps: Vec<String> = Vec::new();
+    for selector in selectors {
+        if !is_pattern(selector) {
+            if !apps.contains(selector) {
+                apps.push(selector.to_string());
+            }
+            continue;
+        }
+
+        let pattern = match Pattern::new(selector) {
+            Ok(p) => p,
+            Err(err) => return Err(format!("Invalid app pattern '{}': {}", selector, err)),
+        };
+
+        let mut matching: Vec<&String> = available
+            .iter()
+            .filter(|name| pattern.matches(name))
+            .collect();
+        if matching.is_empty() {
+            return Err(format!("No app is matching the pattern '{}'", selector));
+        }
+        matching.sort();
+
+        for name in matching {
+            if !apps.contains(name) {
+                apps.push(name.to_string());
+            }
+        }
+    }
+    Ok(apps)
+}
+
+/// Check that the app names of a list of selectors are apps of a team
+///
+/// # Arguments
+/// * `selectors` - A list of app names or glob patterns
+/// * `team` - The name of the team
+/// * `team_apps` - The names of the apps of the team
+///
+/// # Result
+/// * `Result<(), String>` - An Error message naming the first app missing from the team
+///
+pub fn check_team(selectors: &[String], team: &str, team_apps: &[String]) -> Result<(), String> {
+    match selectors
+        .iter()
+        .find(|selector| !is_pattern(selector) && !team_apps.contains(selector))
+    {
+        Some(name) => Err(format!(
+            "App '{}' is not an app of the team '{}'",
+            name, team
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn available() -> Vec<String> {
+        vec![
+            "billing-us".to_string(),
+            "api-eu-staging".to_string(),
+            "billing-eu".to_string(),
+            "api-us-staging".to_string(),
+            "api-us-production".to_string(),
+        ]
+    }
+
+    #[test]
+    fn should_detect_patterns() {
+        assert!(is_pattern("billing-*"));
+        assert!(is_pattern("api-??-staging"));
+        assert!(is_pattern("api-[ue]*"));
+        assert!(!is_pattern("my-fuzzy-app"));
+    }
+
+    #[test]
+    fn should_keep_app_names() {
+        let selectors = vec!["my-fuzzy-app".to_string()];
+        let apps = resolve(&selectors, &available()).unwrap();
+        assert_eq!(apps, vec!["my-fuzzy-app"]);
+    }
+
+    #[test]
+    fn should_expand_patterns() {
+        let selectors = vec!["api-*-staging".to_string(), "billing-*".to_string()];
+        let apps = resolve(&selectors, &available()).unwrap();
+        assert_eq!(
+            apps,
+            vec![
+                "api-eu-staging",
+                "api-us-staging",
+                "billing-eu",
+                "billing-us"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_not_have_duplicates() {
+        let selectors = vec!["billing-us".to_string(), "billing-*".to_string()];
+        let apps = resolve(&selectors, &available()).unwrap();
+        assert_eq!(apps, vec!["billing-us", "billing-eu"]);
+    }
+
+    #[test]
+    fn should_check_the_apps_of_a_team() {
+        let selectors = vec!["billing-us".to_string(), "api-*".to_string()];
+        assert_eq!(check_team(&selectors, "acme", &available()), Ok(()));
+
+        let selectors = vec!["billing-us".to_string(), "my-fuzzy-app".to_string()];
+        assert_eq!(
+            check_team(&selectors, "acme", &available()),
+            Err(String::from(
+                "App 'my-fuzzy-app' is not an app of the team 'acme'"
+            ))
+        );
+    }
+
+    #[test]
+    fn should_fail_when_nothing_matches() {
+        let selectors = vec!["web-*".to_string()];
+        let apps = resolve(&selectors, &available());
+        assert_eq!(
+            apps.err(),
+            Some(String::from("No app is matching the pattern 'web-*'"))
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Compare the config vars of several apps, key by key
+//!
+//! # Example
+//!
+//! ```rust
+//! let rows = compare::compare_config_vars(&[staging_vars, production_vars]);
+//! let differences: Vec<&Row> = rows.iter().filter(|row| row.differs()).collect();
+//! ```
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq)]
+pub struct Row {
+    /// The key of the config var
+    pub key: String,
+    /// The value of the config var for every app, None if it is not set
+    pub values: Vec<Option<String>>,
+}
+
+impl Row {
+    /// Check if the config var is missing or has a different value in one of the apps
+    pub fn differs(&self) -> bool {
+        self.values.windows(2).any(|pair| pair[0] != pair[1])
+    }
+
+    /// Replace the values by a number identifying each distinct value, such as `<1>`
+    ///
+    /// Apps with the same value get the same number, so that the differences are visible
+    /// without printing secrets.
+    pub fn masked_values(&self) -> Vec<Option<String>> {
+        let mut distinct: Vec<&String> = Vec::new();
+        self.values
+            .iter()
+            .map(|value| {
+                value.as_ref().map(|value| {
+                    let position = match distinct.iter().position(|v| *v == value) {
+                        Some(position) => position,
+                        None => {
+                            distinct.push(value);
+                            distinct.len() - 1
+                        }
+                    };
+                    format!("<{}>", position + 1)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Compare the config vars of several apps
+///
+/// # Arguments
+/// * `apps` - The config vars of every app
+///
+/// # Result
+/// * `Vec<Row>` - A row for every key set in one of the apps, sorted by key
+///
+pub fn compare_config_vars(apps: &[BTreeMap<String, String>]) -> Vec<Row> {
+    let mut keys: Vec<&String> = apps
+        .iter()
+        .flat_map(|config_vars| config_vars.keys())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .map(|key| Row {
+            key: key.to_string(),
+            values: apps
+                .iter()
+                .map(|config_vars| config_vars.get(key).cloned())
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_compare_every_key() {
+        let staging = vars(&[("ENV", "staging"), ("PORT", "5000")]);
+        let production = vars(&[("ENV", "production"), ("PORT", "5000"), ("SENTRY_DSN", "x")]);
+        let rows = compare_config_vars(&[staging, production]);
+        assert_eq!(
+            rows,
+            vec![
+                Row {
+                    key: "ENV".to_string(),
+                    values: vec![Some("staging".to_string()), Some("production".to_string())],
+                },
+                Row {
+                    key: "PORT".to_string(),
+                    values: vec![Some("5000".to_string()), Some("5000".to_string())],
+                },
+                Row {
+                    key: "SENTRY_DSN".to_string(),
+                    values: vec![None, Some("x".to_string())],
+                },
+            ]
+        );
+        assert!(rows[0].differs());
+        assert!(!rows[1].differs());
+        assert!(rows[2].differs());
+    }
+
+    #[test]
+    fn should_mask_values() {
+        let row = Row {
+            key: "DATABASE_URL".to_string(),
+            values: vec![
+                Some("postgres://a".to_string()),
+                None,
+                Some("postgres://b".to_string()),
+                Some("postgres://a".to_string()),
+            ],
+        };
+        assert_eq!(
+            row.masked_values(),
+            vec![
+                Some("<1>".to_string()),
+                None,
+                Some("<2>".to_string()),
+                Some("<1>".to_string()),
+            ]
+        );
+    }
+}
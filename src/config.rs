@@ -3,7 +3,9 @@
 //! # Example
 //!
 //! ```rust
-//! let c = Config::from_path("/home/john/test.yml");
+//! use heroku_env::config::Config;
+//!
+//! let c = Config::from_path("tests/config_v2_test.yml".to_string());
 //! match c {
 //!     Ok(hc) => println!("Config data: {:?}", hc),
 //!     Err(err) => println!("Error: {}", err),
@@ -11,31 +13,188 @@
 //! ```
 use std;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
 
-use std::fs::File;
-use std::io::Read;
-use std::io::Write;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process;
 
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
 use serde_yaml;
 
-static VERSION: &str = "1";
+use keys::KeyFilter;
+use merge;
+use migration;
+use schema::Rule;
+use sources::{Source, DIRECTIVES};
+use validation;
+use yaml::{self, Node};
+
+static VERSION: &str = "2";
+
+/// Versions of the YAML file that can still be loaded
+static SUPPORTED_VERSIONS: &[&str] = &["1", "2"];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     /// The version of the YAML file
     pub version: String,
+    /// Key-value pair of settings shared by every app (version 2)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defaults: BTreeMap<String, String>,
+    /// Values of the defaults given by a source directive, read by `resolve_sources`
+    #[serde(skip)]
+    pub resolved_defaults: BTreeMap<String, String>,
+    /// Rules of the config vars of every app, by key (version 2)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schema: BTreeMap<String, Rule>,
     /// The list of apps
     pub apps: Vec<App>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// An app of the config file, its settings given by a source directive are read from `sources`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "AppFile", into = "AppFile")]
 pub struct App {
     /// The name of an app
     pub name: String,
     /// Key-value pair of settings
+    pub settings: BTreeMap<String, String>,
+    /// Sources of the settings read from outside of the config file, by key
+    pub sources: BTreeMap<String, Source>,
+    /// Keys of the config vars to remove
+    pub unset: Vec<String>,
+    /// Require typing the name of the app to confirm a push
+    pub protected: bool,
+}
+
+/// An app as written in the config file, with the values and source directives of its settings
+#[derive(Serialize, Deserialize)]
+struct AppFile {
+    name: String,
     #[serde(default)]
-    pub settings: HashMap<String, String>,
+    settings: BTreeMap<String, Setting>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unset: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    protected: bool,
+}
+
+/// The value of a setting in the config file, or the directive reading it from a source
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Setting {
+    Value(String),
+    Source(Source),
+}
+
+impl From<AppFile> for App {
+    fn from(file: AppFile) -> Self {
+        let mut app = App::new();
+        app.name = file.name;
+        app.unset = file.unset;
+        app.protected = file.protected;
+        for (key, setting) in file.settings {
+            match setting {
+                Setting::Value(value) => match Source::from_value(&value) {
+                    Some(source) => {
+                        app.sources.insert(key, source);
+                    }
+                    None => {
+                        app.settings.insert(key, value);
+                    }
+                },
+                Setting::Source(source) => {
+                    app.sources.insert(key, source);
+                }
+            }
+        }
+        app
+    }
+}
+
+impl From<App> for AppFile {
+    fn from(app: App) -> Self {
+        // The resolved values of sourced settings are never written back
+        let mut settings: BTreeMap<String, Setting> = app
+            .settings
+            .into_iter()
+            .map(|(key, value)| (key, Setting::Value(value)))
+            .collect();
+        for (key, source) in app.sources {
+            let setting = match source {
+                Source::Vault(_) => Setting::Value(source.to_string()),
+                _ => Setting::Source(source),
+            };
+            settings.insert(key, setting);
+        }
+        AppFile {
+            name: app.name,
+            settings,
+            unset: app.unset,
+            protected: app.protected,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Setting {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(SettingVisitor)
+    }
+}
+
+/// Read scalars as values, and maps as source directives
+///
+/// Numbers lose their formatting here, the raw text of the values is restored from the file by
+/// `Config::from_path`.
+struct SettingVisitor;
+
+impl<'de> Visitor<'de> for SettingVisitor {
+    type Value = Setting;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a value or one of the directives `{}`",
+            DIRECTIVES.join("`, `")
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Setting, E> {
+        Ok(Setting::Value(value.to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Setting, E> {
+        Ok(Setting::Value(value.to_string()))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Setting, E> {
+        Ok(Setting::Value(value.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Setting, E> {
+        Ok(Setting::Value(value.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Setting, E> {
+        Ok(Setting::Value(value.to_string()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Setting, E> {
+        Ok(Setting::Value(String::new()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Setting, A::Error> {
+        Source::deserialize(MapAccessDeserializer::new(map)).map(Setting::Source)
+    }
 }
 
 impl App {
@@ -46,12 +205,106 @@ impl App {
     ///
     pub fn new() -> Self {
         let app_name: String = String::new();
-        let settings: HashMap<String, String> = HashMap::new();
+        let settings: BTreeMap<String, String> = BTreeMap::new();
         App {
             name: app_name,
             settings,
+            sources: BTreeMap::new(),
+            unset: Vec::new(),
+            protected: false,
         }
     }
+
+    /// Check if the app has neither config vars to set nor to remove
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty() && self.sources.is_empty() && self.unset.is_empty()
+    }
+
+    /// Merge the settings and removals of another app into this one
+    ///
+    /// The changes of the other app take precedence: a key it sets is no longer removed, and a
+    /// key it removes is no longer set. The app stays protected if one of them is.
+    ///
+    /// # Arguments
+    /// * `other` - The app to merge
+    ///
+    pub fn merge(&mut self, other: App) {
+        self.protected = self.protected || other.protected;
+        for key in other.unset {
+            self.settings.remove(&key);
+            self.sources.remove(&key);
+            if !self.unset.contains(&key) {
+                self.unset.push(key);
+            }
+        }
+        for (key, value) in other.settings {
+            self.unset.retain(|k| *k != key);
+            self.sources.remove(&key);
+            self.settings.insert(key, value);
+        }
+        for (key, source) in other.sources {
+            self.unset.retain(|k| *k != key);
+            self.sources.insert(key, source);
+        }
+    }
+
+    /// List every change to apply to the config vars of the app, in a single batch
+    ///
+    /// # Result
+    /// * `BTreeMap<String, Option<String>>` - The new value of each config var, `None` to remove it
+    ///
+    pub fn changes(&self) -> BTreeMap<String, Option<String>> {
+        let mut changes: BTreeMap<String, Option<String>> = BTreeMap::new();
+        for key in &self.unset {
+            changes.insert(key.to_string(), None);
+        }
+        for (key, value) in &self.settings {
+            changes.insert(key.to_string(), Some(value.to_string()));
+        }
+        changes
+    }
+
+    /// Add the default values of a schema for the config vars missing from the app on heroku
+    ///
+    /// A default value is only pushed if the app neither sets nor removes the config var and
+    /// heroku has no value for it, so a value set on heroku on purpose is kept.
+    ///
+    /// # Arguments
+    /// * `schema` - The rules of the config vars
+    /// * `remote` - The config vars of the app on heroku, empty for a new deployment
+    ///
+    pub fn apply_schema_defaults(
+        &mut self,
+        schema: &BTreeMap<String, Rule>,
+        remote: &BTreeMap<String, String>,
+    ) {
+        for (key, rule) in schema {
+            if let Some(ref value) = rule.default {
+                if !self.is_set(key) && !remote.contains_key(key) {
+                    self.settings.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+    }
+
+    /// Check if the app sets or removes a config var
+    fn is_set(&self, key: &str) -> bool {
+        self.settings.contains_key(key)
+            || self.sources.contains_key(key)
+            || self.unset.iter().any(|k| k == key)
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        App::new()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
 }
 
 impl Config {
@@ -64,39 +317,166 @@ impl Config {
         let app_list: Vec<App> = Vec::new();
         Config {
             version: VERSION.to_string(),
+            defaults: BTreeMap::new(),
+            resolved_defaults: BTreeMap::new(),
+            schema: BTreeMap::new(),
             apps: app_list,
         }
     }
-    /// Initialize a configuration structure from an app and settings
+    /// Initialize a configuration structure sharing the same settings between several apps
     ///
     /// # Arguments
-    /// * `app_name` - An app name
-    /// * `settings` - A HashMap containing the settings of the app
+    /// * `app_names` - A list of app names
+    /// * `settings` - A BTreeMap containing the settings of every app
     ///
     /// # Result
     /// * `Result<Config, String>` - A config structure or an Error message
     ///
     /// # Example
     /// ```rust
-    /// let mut settings: HashMap<String, String> = HashMap::new();
-    /// settings.insert("MY_VAR", "my_value");
-    /// let app_name = String::from("app-name");
-    /// let conf = Config::from_app(&app_name, settings);
-    /// assert!(conf.is_ok());
+    /// # use heroku_env::config::Config;
+    /// # use std::collections::BTreeMap;
+    /// let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    /// settings.insert("MY_VAR".to_string(), "my_value".to_string());
+    /// let app_names = vec![String::from("app-name"), String::from("other-app-name")];
+    /// let conf = Config::from_apps(&app_names, settings);
+    /// assert_eq!(conf.unwrap().apps.len(), 2);
     /// ```
-    pub fn from_app(app_name: &str, settings: HashMap<String, String>) -> Result<Self, String> {
-        let apps: App = App {
-            name: app_name.to_string(),
-            settings,
-        };
-        let app_list = vec![apps];
+    pub fn from_apps(
+        app_names: &[String],
+        settings: BTreeMap<String, String>,
+    ) -> Result<Self, String> {
+        let app_list: Vec<App> = app_names
+            .iter()
+            .map(|app_name| App {
+                name: app_name.to_string(),
+                settings: settings.clone(),
+                sources: BTreeMap::new(),
+                unset: Vec::new(),
+                protected: false,
+            })
+            .collect();
         let config: Config = Config {
             version: VERSION.to_string(),
+            defaults: BTreeMap::new(),
+            resolved_defaults: BTreeMap::new(),
+            schema: BTreeMap::new(),
             apps: app_list,
         };
         Ok(config)
     }
 
+    /// Merge the apps of another configuration into this one
+    ///
+    /// Apps already present are merged with the ones of the other configuration (see
+    /// `App::merge`), so that every app is listed only once.
+    ///
+    /// # Arguments
+    /// * `other` - The configuration to merge
+    ///
+    /// # Example
+    /// ```rust
+    /// # use heroku_env::config::Config;
+    /// let mut conf = Config::new();
+    /// conf.merge(Config::from_path("tests/config_v2_test.yml".to_string()).unwrap());
+    /// ```
+    pub fn merge(&mut self, other: Config) {
+        for app in other.apps {
+            match self.apps.iter().position(|a| a.name == app.name) {
+                Some(index) => self.apps[index].merge(app),
+                None => self.apps.push(app),
+            }
+        }
+    }
+
+    /// Apply the default settings to every app
+    ///
+    /// The settings, sources and removals of an app take precedence over the defaults, which are
+    /// then emptied. A default given by a source directive is applied with the value read by
+    /// `resolve_sources`, or as a source of the apps if it was not read. The default values of the
+    /// schema depend on the config vars of the apps on heroku, they are added by
+    /// `App::apply_schema_defaults`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use heroku_env::config::Config;
+    /// let mut conf = Config::from_path("tests/config_v2_test.yml".to_string()).unwrap();
+    /// conf.apply_defaults();
+    /// assert!(conf.defaults.is_empty());
+    /// ```
+    pub fn apply_defaults(&mut self) {
+        for app in &mut self.apps {
+            for (key, value) in &self.defaults {
+                if app.is_set(key) {
+                    continue;
+                }
+                match (self.resolved_defaults.get(key), Source::from_value(value)) {
+                    (None, Some(source)) => {
+                        app.sources.insert(key.to_string(), source);
+                    }
+                    (resolved, _) => {
+                        let value = resolved.unwrap_or(value);
+                        app.settings.insert(key.to_string(), value.to_string());
+                    }
+                }
+            }
+        }
+        self.defaults.clear();
+        self.resolved_defaults.clear();
+    }
+
+    /// Check if the configuration uses an older version of the YAML file
+    pub fn is_outdated(&self) -> bool {
+        self.version != VERSION
+    }
+
+    /// Upgrade a config file to the latest version of the YAML file
+    ///
+    /// The file is edited in place, keeping its comments and formatting.
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the config file in YAML format
+    /// * `output` - A string containing the path to write the upgraded file into
+    /// * `force` - Overwrite the output file if it exists and is not the config file itself
+    ///
+    /// # Result
+    /// * `Result<String, String>` - A success string or an Error message
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use heroku_env::config::Config;
+    /// let result = Config::migrate("/home/john/test.yml".to_string(), "/home/john/test.yml", false);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn migrate(path: String, output: &str, force: bool) -> Result<String, String> {
+        let yaml_file = match Config::load(path.to_string()) {
+            Ok(f) => f,
+            Err(err) => return Err(err.to_string()),
+        };
+
+        let version = migration::read_version(&yaml_file)?.0;
+        if version == VERSION {
+            return Ok(format!("{} is already in version {}", path, VERSION));
+        }
+
+        let upgraded = migration::migrate(&yaml_file, VERSION)?;
+        let problems = validation::validate(&upgraded, SUPPORTED_VERSIONS);
+        if let Some(problem) = problems.first() {
+            return Err(format!("{}:{}", path, problem));
+        }
+
+        if output != path {
+            Config::check_overwrite(output, force).map_err(|err| err.to_string())?;
+        }
+        match Config::write(output, &upgraded) {
+            Ok(_) => Ok(format!(
+                "Successfully migrated {} from version {} to version {} at {}",
+                path, version, VERSION, output
+            )),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
     /// Load a configuration structure from a file path
     ///
     /// # Arguments
@@ -107,54 +487,369 @@ impl Config {
     ///
     /// # Example
     /// ```rust
-    /// let conf = Config::from_path("/home/john/test.yml");
+    /// # use heroku_env::config::Config;
+    /// let conf = Config::from_path("tests/config_v2_test.yml".to_string());
     /// assert!(conf.is_ok());
     /// ```
     pub fn from_path(path: String) -> Result<Self, String> {
-        let yaml_file = match Config::load(path) {
+        let yaml_file = match Config::load(path.to_string()) {
             Ok(f) => f,
             Err(err) => return Err(err.to_string()),
         };
 
-        let config: Config = match serde_yaml::from_str(&yaml_file) {
+        let mut config: Config = match serde_yaml::from_str(&yaml_file) {
             Ok(c) => c,
             Err(err) => return Err(err.to_string()),
         };
 
+        let problems = validation::validate(&yaml_file, SUPPORTED_VERSIONS);
+        if !problems.is_empty() {
+            let messages: Vec<String> = problems
+                .iter()
+                .map(|problem| format!("{}:{}", path, problem))
+                .collect();
+            return Err(messages.join("\n"));
+        }
+
+        if let Ok(Some(root)) = yaml::parse(&yaml_file) {
+            config.restore_raw_settings(&root);
+        }
         Ok(config)
     }
 
+    /// Keep the values of the settings as written in the file, `007` or `1.10` not being numbers
+    fn restore_raw_settings(&mut self, root: &Node) {
+        let apps = match root.get("apps") {
+            Some(Node::Sequence(apps, _)) => apps,
+            _ => return,
+        };
+        for (app, node) in self.apps.iter_mut().zip(apps) {
+            if let Some(Node::Mapping(settings, _)) = node.get("settings") {
+                for (key, value) in settings {
+                    if let (Some(key), Node::Scalar(raw, _)) = (key.as_str(), value) {
+                        if !app.sources.contains_key(key) {
+                            app.settings.insert(key.to_string(), raw.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read the settings given by a source directive, from files, env vars, commands or Vault
+    ///
+    /// The values are only kept in memory: saving the configuration writes the directives back.
+    /// The defaults referencing a secret of Vault are read as well.
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the config file, relative paths of files
+    ///   start from its directory
+    ///
+    /// # Result
+    /// * `Result<(), String>` - Nothing, or an Error message for every setting that could not be
+    ///   read
+    ///
+    /// # Example
+    /// ```rust
+    /// # use heroku_env::config::Config;
+    /// let mut conf = Config::from_path("tests/config_v2_test.yml".to_string()).unwrap();
+    /// assert!(conf.resolve_sources("tests/config_v2_test.yml").is_ok());
+    /// ```
+    pub fn resolve_sources(&mut self, path: &str) -> Result<(), String> {
+        let base = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+        let mut errors: Vec<String> = Vec::new();
+        for (key, value) in &self.defaults {
+            if let Some(source) = Source::from_value(value) {
+                match source.resolve(base) {
+                    Ok(resolved) => {
+                        self.resolved_defaults.insert(key.to_string(), resolved);
+                    }
+                    Err(err) => errors.push(format!("defaults: {}: {}", key, err)),
+                }
+            }
+        }
+        for app in &mut self.apps {
+            for (key, source) in &app.sources {
+                match source.resolve(base) {
+                    Ok(value) => {
+                        app.settings.insert(key.to_string(), value);
+                    }
+                    Err(err) => errors.push(format!("{}: {}: {}", app.name, key, err)),
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Validate a config file, without loading it
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the config file in YAML format
+    ///
+    /// # Result
+    /// * `Result<Vec<String>, String>` - Every problem found, prefixed with the file, line and
+    ///   column where it was found, or an Error message if the file can not be read
+    ///
+    /// # Example
+    /// ```rust
+    /// # use heroku_env::config::Config;
+    /// let problems = Config::validate("tests/config_v2_test.yml".to_string());
+    /// assert!(problems.unwrap().is_empty());
+    /// ```
+    pub fn validate(path: String) -> Result<Vec<String>, String> {
+        let yaml_file = match Config::load(path.to_string()) {
+            Ok(f) => f,
+            Err(err) => return Err(err.to_string()),
+        };
+
+        let mut messages: Vec<String> = validation::validate(&yaml_file, SUPPORTED_VERSIONS)
+            .iter()
+            .map(|problem| format!("{}:{}", path, problem))
+            .collect();
+        if messages.is_empty() {
+            // Not expected, but the deserialization stays the source of truth
+            if let Err(err) = serde_yaml::from_str::<Config>(&yaml_file) {
+                messages.push(format!("{}: {}", path, err));
+            }
+        }
+        Ok(messages)
+    }
+
     /// Save a configuration struct to a YAML file
     ///
+    /// The file is replaced atomically, and only readable by its owner when it is created.
+    ///
     /// # Arguments
     /// * `path` - A string containing the path to store the config file
+    /// * `force` - Overwrite the file if it already exists
     ///
     /// # Result
     /// * `Result<String, std::io::Error>` - A success string or an Error message
     ///
     /// # Example
-    /// ```rust
-    /// let mut settings: HashMap<String, String> = HashMap::new();
-    /// settings.insert("MY_VAR", "my_value");
+    /// ```rust,no_run
+    /// # use heroku_env::config::Config;
+    /// # use std::collections::BTreeMap;
+    /// let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    /// settings.insert("MY_VAR".to_string(), "my_value".to_string());
     /// let app_name = String::from("app-name");
-    /// let conf = Config::from_app(&app-name, settings).unwrap();
-    /// let result = conf.save("my-dir/myfile.yml");
+    /// let mut conf = Config::from_apps(&[app_name], settings).unwrap();
+    /// let result = conf.save("my-dir/myfile.yml", false);
     /// assert_eq!(result.unwrap(), "Successfully created config file at my-dir/myfile.yml");
     /// ```
-    pub fn save(&mut self, path: &str) -> Result<String, std::io::Error> {
+    pub fn save(&mut self, path: &str, force: bool) -> Result<String, std::io::Error> {
+        Config::check_overwrite(path, force)?;
         let yaml_buffer = serde_yaml::to_string(&self).unwrap();
-        match File::create(&path) {
-            Ok(mut f) => match f.write(&yaml_buffer.into_bytes()) {
-                Ok(_success) => {
-                    let output: String = format!("Successfully created config file at {}", &path);
-                    Ok(output)
-                }
-                Err(err) => Err(err),
-            },
+        match Config::write(path, &yaml_buffer) {
+            Ok(_success) => {
+                let output: String = format!("Successfully created config file at {}", &path);
+                Ok(output)
+            }
             Err(err) => Err(err),
         }
     }
 
+    /// Merge the apps of a configuration struct into an existing YAML file
+    ///
+    /// Only the settings of the apps of the configuration are updated, the other apps and the
+    /// comments of the file are kept. The file is created if it does not exist.
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the config file
+    /// * `keys` - The keys of the settings of the apps, the other settings of the file are kept
+    ///
+    /// # Result
+    /// * `Result<String, String>` - A success string or an Error message
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use heroku_env::config::Config;
+    /// # use heroku_env::keys::KeyFilter;
+    /// # use std::collections::BTreeMap;
+    /// let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    /// settings.insert("MY_VAR".to_string(), "my_value".to_string());
+    /// let mut conf = Config::from_apps(&["app-name".to_string()], settings).unwrap();
+    /// let result = conf.save_merged("my-dir/myfile.yml", &KeyFilter::all());
+    /// assert_eq!(result.unwrap(), "Successfully merged app-name into config file at my-dir/myfile.yml");
+    /// ```
+    pub fn save_merged(&mut self, path: &str, keys: &KeyFilter) -> Result<String, String> {
+        if !Path::new(path).exists() {
+            return self.save(path, false).map_err(|err| err.to_string());
+        }
+        let yaml_file = match Config::load(path.to_string()) {
+            Ok(f) => f,
+            Err(err) => return Err(err.to_string()),
+        };
+
+        let merged = merge::merge_apps(&yaml_file, &self.apps, keys)?;
+        let problems = validation::validate(&merged, SUPPORTED_VERSIONS);
+        if let Some(problem) = problems.first() {
+            return Err(format!("{}:{}", path, problem));
+        }
+
+        let names: Vec<&str> = self.apps.iter().map(|app| app.name.as_str()).collect();
+        match Config::write(path, &merged) {
+            Ok(_) => Ok(format!(
+                "Successfully merged {} into config file at {}",
+                names.join(", "),
+                path
+            )),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Write config vars imported into an app into an existing YAML file
+    ///
+    /// Like `save_merged`, the file is edited in place: only the imported settings of the app
+    /// are written, replacing their source directives and removals, and the comments of the file
+    /// are kept. The file is created if it does not exist.
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the config file
+    /// * `app_name` - The name of the app the config vars are imported into
+    /// * `keys` - The keys of the imported config vars
+    ///
+    /// # Result
+    /// * `Result<String, String>` - A success string or an Error message
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use heroku_env::config::Config;
+    /// # use std::collections::BTreeMap;
+    /// let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    /// settings.insert("MY_VAR".to_string(), "my_value".to_string());
+    /// let mut conf = Config::from_apps(&["app-name".to_string()], settings).unwrap();
+    /// let result = conf.save_imported("my-dir/myfile.yml", "app-name", &["MY_VAR".to_string()]);
+    /// assert_eq!(result.unwrap(), "Successfully merged app-name into config file at my-dir/myfile.yml");
+    /// ```
+    pub fn save_imported(
+        &mut self,
+        path: &str,
+        app_name: &str,
+        keys: &[String],
+    ) -> Result<String, String> {
+        if !Path::new(path).exists() {
+            return self.save(path, false).map_err(|err| err.to_string());
+        }
+        let mut imported = App::new();
+        imported.name = app_name.to_string();
+        if let Some(app) = self.apps.iter().find(|app| app.name == app_name) {
+            for key in keys {
+                if let Some(value) = app.settings.get(key) {
+                    imported.settings.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+        if imported.settings.is_empty() {
+            return Ok(format!("No config vars to import into {}", path));
+        }
+
+        let yaml_file = Config::load(path.to_string()).map_err(|err| err.to_string())?;
+        let content = merge::remove_keys(&yaml_file, app_name, keys)?;
+        let filter: Vec<&str> = imported.settings.keys().map(|key| key.as_str()).collect();
+        let merged = merge::merge_apps(
+            &content,
+            std::slice::from_ref(&imported),
+            &KeyFilter::new(&filter, &[])?,
+        )?;
+        let problems = validation::validate(&merged, SUPPORTED_VERSIONS);
+        if let Some(problem) = problems.first() {
+            return Err(format!("{}:{}", path, problem));
+        }
+        // Settings in the flow style with source directives can not be edited in place
+        let written: Config = serde_yaml::from_str(&merged).map_err(|err| err.to_string())?;
+        let app = written.apps.iter().find(|app| app.name == app_name);
+        let is_written = |(key, value): (&String, &String)| match app {
+            Some(app) => app.settings.get(key) == Some(value) && !app.unset.contains(key),
+            None => false,
+        };
+        if !imported.settings.iter().all(is_written) {
+            return Err(format!(
+                "{}: the settings of app {} can not be edited in place, write them in the block \
+                 style (one setting per line)",
+                path, app_name
+            ));
+        }
+
+        match Config::write(path, &merged) {
+            Ok(_) => Ok(format!(
+                "Successfully merged {} into config file at {}",
+                app_name, path
+            )),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Helper method to refuse to overwrite an existing file, unless forced
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to a file
+    /// * `force` - Allow to overwrite the file
+    ///
+    /// # Result
+    /// * `Result<(), Error>` - Nothing, or an io::Error if the file exists
+    ///
+    pub fn check_overwrite(path: &str, force: bool) -> Result<(), io::Error> {
+        if !force && Path::new(path).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists, use --force to overwrite it", path),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Helper method to write a String into a file atomically
+    ///
+    /// The content is written into a temporary file next to the file, which then replaces it,
+    /// so that an interruption never leaves a truncated file. A new file is only readable by its
+    /// owner as config vars often contain secrets, an existing file keeps its permissions.
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to a file
+    /// * `content` - The content of the file
+    ///
+    /// # Result
+    /// * `Result<(), Error>` - Nothing, or an io::Error
+    ///
+    pub fn write(path: &str, content: &str) -> Result<(), io::Error> {
+        let target = Path::new(path);
+        let file_name = match target.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a file", path),
+                ))
+            }
+        };
+        let temp = target.with_file_name(format!(".{}.{}.tmp", file_name, process::id()));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let result = options
+            .open(&temp)
+            .and_then(|mut f| {
+                f.write_all(content.as_bytes())?;
+                f.sync_all()
+            })
+            .and_then(|_| match fs::metadata(target) {
+                Ok(metadata) => fs::set_permissions(&temp, metadata.permissions()),
+                Err(_) => Ok(()),
+            })
+            .and_then(|_| fs::rename(&temp, target));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
     /// Helper method to load the content of a file into a String
     ///
     /// # Arguments
@@ -163,7 +858,7 @@ impl Config {
     /// # Result
     /// * `Result<String, Error>` - The content of a file or an io::Error
     ///
-    fn load(path: String) -> Result<String, std::io::Error> {
+    pub fn load(path: String) -> Result<String, std::io::Error> {
         match File::open(path) {
             Ok(mut f) => {
                 let mut data = String::new();
@@ -175,20 +870,129 @@ impl Config {
         }
     }
 }
+/// Check if a flag is not set, to leave it out of a config file
+fn is_false(flag: &bool) -> bool {
+    !*flag
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::env;
     use std::fs;
 
     #[test]
-    fn should_instanciate_from_app() {
+    fn should_instanciate_from_a_single_app() {
         let app_name = String::from("my-fuzzy-app");
-        let mut settings: HashMap<String, String> = HashMap::new();
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
         settings.insert("MY_VAR".to_string(), "my_value".to_string());
-        let config = Config::from_app(&app_name, settings);
+        let config = Config::from_apps(&[app_name], settings);
         assert!(config.is_ok());
     }
 
+    #[test]
+    fn should_instanciate_from_apps() {
+        let app_names = vec![String::from("my-fuzzy-app"), String::from("my-other-app")];
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
+        settings.insert("MY_VAR".to_string(), "my_value".to_string());
+        let config = Config::from_apps(&app_names, settings).unwrap();
+        assert_eq!(config.apps.len(), 2);
+        assert_eq!(config.apps[1].name, "my-other-app");
+        assert_eq!(config.apps[1].settings["MY_VAR"], "my_value");
+    }
+
+    #[test]
+    fn should_merge_apps() {
+        let path = String::from("tests/config_test.yml");
+        let mut config = Config::new();
+        config.merge(Config::from_path(path).unwrap());
+        config.apps[0].protected = true;
+
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
+        settings.insert("TEST_VAR2".to_string(), "20".to_string());
+        let mut other = Config::from_apps(&["fuzzy-test-app".to_string()], settings).unwrap();
+        other.apps[0].unset.push("TEST_VAR".to_string());
+        config.merge(other);
+
+        assert_eq!(config.apps.len(), 2);
+        let app = &config.apps[0];
+        assert_eq!(app.settings.len(), 1);
+        assert_eq!(app.settings["TEST_VAR2"], "20");
+        assert_eq!(app.unset, vec!["TEST_VAR"]);
+        assert!(app.protected);
+    }
+
+    #[test]
+    fn should_batch_changes() {
+        let mut app = App::new();
+        app.unset.push("OLD_VAR".to_string());
+        app.unset.push("MY_VAR".to_string());
+        app.settings
+            .insert("MY_VAR".to_string(), "my_value".to_string());
+        let changes = app.changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes["OLD_VAR"], None);
+        assert_eq!(changes["MY_VAR"], Some("my_value".to_string()));
+    }
+
+    #[test]
+    fn should_apply_defaults() {
+        let path = String::from("tests/config_v2_test.yml");
+        let mut config = Config::from_path(path).unwrap();
+        assert!(!config.is_outdated());
+        config.apply_defaults();
+        assert!(config.defaults.is_empty());
+
+        let first_app = &config.apps[0];
+        assert_eq!(first_app.settings["LOG_LEVEL"], "debug");
+        assert_eq!(first_app.settings["TIMEOUT"], "30");
+        let second_app = &config.apps[1];
+        assert_eq!(second_app.settings["LOG_LEVEL"], "info");
+        assert!(!second_app.settings.contains_key("TIMEOUT"));
+    }
+
+    #[test]
+    fn should_apply_schema_defaults() {
+        let path = String::from("tests/config_schema_test.yml");
+        let mut config = Config::from_path(path).unwrap();
+        assert_eq!(config.schema.len(), 3);
+        assert!(config.schema["PORT"].required);
+        config.apply_defaults();
+        assert!(!config.apps[1].settings.contains_key("LOG_LEVEL"));
+
+        let mut remote: BTreeMap<String, String> = BTreeMap::new();
+        let schema = &config.schema;
+        for app in &mut config.apps {
+            app.apply_schema_defaults(schema, &remote);
+        }
+        assert_eq!(config.apps[0].settings["LOG_LEVEL"], "verbose");
+        assert_eq!(config.apps[1].settings["LOG_LEVEL"], "info");
+        assert!(!config.apps[2].settings.contains_key("LOG_LEVEL"));
+
+        let mut app = App::new();
+        remote.insert("LOG_LEVEL".to_string(), "warn".to_string());
+        app.apply_schema_defaults(schema, &remote);
+        assert!(app.settings.is_empty());
+    }
+
+    #[test]
+    fn should_migrate_config_file() {
+        let path = String::from("tests/config_test.yml");
+        let config = Config::from_path(path.to_string()).unwrap();
+        assert!(config.is_outdated());
+
+        let result = Config::migrate(path, "tests/migrated_test.yml", false);
+        assert_eq!(
+            result.unwrap(),
+            "Successfully migrated tests/config_test.yml from version 1 to version 2 at tests/migrated_test.yml"
+        );
+        let migrated = Config::from_path(String::from("tests/migrated_test.yml")).unwrap();
+        assert_eq!(migrated.version, "2");
+        assert_eq!(migrated.apps.len(), 2);
+        assert_eq!(migrated.apps[0].settings["TEST_VAR"], "my custom value");
+        fs::remove_file("tests/migrated_test.yml").unwrap();
+    }
+
     #[test]
     fn is_in_version_1() {
         let path = String::from("tests/config_test.yml");
@@ -247,19 +1051,236 @@ mod tests {
         assert_eq!(config.err(), Some(msg));
     }
 
+    #[test]
+    fn should_report_invalid_config() {
+        let path = String::from("tests/config_invalid_test.yml");
+        let config = Config::from_path(path);
+        assert_eq!(
+            config.err(),
+            Some(String::from(
+                "tests/config_invalid_test.yml:1:10: unsupported version \"7\", expected one of \"1\", \"2\"\n\
+                 tests/config_invalid_test.yml:7:11: duplicate app name \"fuzzy-test-app\", first defined at line 3"
+            ))
+        );
+    }
+
+    #[test]
+    fn should_validate_config_file() {
+        let path = String::from("tests/config_test.yml");
+        assert!(Config::validate(path).unwrap().is_empty());
+
+        let path = String::from("tests/config_wrong_test.yml");
+        let problems = Config::validate(path).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0],
+            "tests/config_wrong_test.yml:1:1: missing field `apps`"
+        );
+    }
+
     #[test]
     fn should_be_saved_on_disk() {
         let app_name = String::from("my-fuzzy-app");
-        let mut settings: HashMap<String, String> = HashMap::new();
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
         settings.insert("MY_VAR".to_string(), "my_value".to_string());
-        let mut config = Config::from_app(&app_name, settings).unwrap();
-        let result = config.save("tests/test.yml");
+        let mut config = Config::from_apps(&[app_name], settings).unwrap();
+        let result = config.save("tests/test.yml", true);
         assert_eq!(
             result.unwrap(),
             "Successfully created config file at tests/test.yml"
         );
     }
 
+    #[test]
+    fn should_merge_into_file_on_disk() {
+        let path = "tests/merged_test.yml";
+        fs::copy("tests/config_v2_test.yml", path).unwrap();
+
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
+        settings.insert("LOG_LEVEL".to_string(), "warn".to_string());
+        settings.insert("TIMEOUT".to_string(), "30".to_string());
+        let mut config = Config::from_apps(&["fuzzy-test-app".to_string()], settings).unwrap();
+        let result = config.save_merged(path, &KeyFilter::all());
+        assert_eq!(
+            result.unwrap(),
+            "Successfully merged fuzzy-test-app into config file at tests/merged_test.yml"
+        );
+
+        let merged = Config::from_path(path.to_string()).unwrap();
+        assert_eq!(merged.apps.len(), 2);
+        assert_eq!(merged.apps[0].settings.len(), 1);
+        assert_eq!(merged.apps[0].settings["LOG_LEVEL"], "warn");
+        assert_eq!(merged.apps[1].unset, vec!["TIMEOUT"]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_import_into_file_on_disk() {
+        let path = "tests/imported_test.yml";
+        let content = "version: \"2\" # shared by every app\napps:\n  # Production\n  - name: \"fuzzy-test-app\"\n    settings:\n      LOG_LEVEL: \"debug\" # verbose\n    unset:\n      - TIMEOUT\n";
+        fs::write(path, content).unwrap();
+
+        let mut config = Config::from_path(path.to_string()).unwrap();
+        let app = &mut config.apps[0];
+        app.settings.insert("TIMEOUT".to_string(), "30".to_string());
+        app.settings.insert("PORT".to_string(), "5000".to_string());
+        app.unset.clear();
+        let keys = vec!["TIMEOUT".to_string(), "PORT".to_string()];
+        assert_eq!(
+            config.save_imported(path, "fuzzy-test-app", &keys).unwrap(),
+            "Successfully merged fuzzy-test-app into config file at tests/imported_test.yml"
+        );
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "version: \"2\" # shared by every app\napps:\n  # Production\n  - name: \"fuzzy-test-app\"\n    settings:\n      LOG_LEVEL: \"debug\" # verbose\n      PORT: \"5000\"\n      TIMEOUT: \"30\"\n"
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_resolve_sources() {
+        let path = "tests/config_sources_test.yml";
+        let mut config = Config::from_path(path.to_string()).unwrap();
+        assert_eq!(config.apps[0].settings["VERSION"], "1.10");
+        assert_eq!(config.apps[0].settings["ZIP_CODE"], "007");
+        assert_eq!(
+            config.apps[0].sources["STRIPE_KEY"],
+            Source::File("./sources_test.key".to_string())
+        );
+        config.apply_defaults();
+        assert!(!config.apps[0].settings.contains_key("STRIPE_KEY"));
+
+        assert_eq!(
+            config.resolve_sources(path),
+            Err(
+                "fuzzy-test-app: SENTRY_DSN: environment variable HKE_CONFIG_SOURCES_DSN is not set"
+                    .to_string()
+            )
+        );
+        env::set_var("HKE_CONFIG_SOURCES_DSN", "https://sentry.io/1");
+        config.resolve_sources(path).unwrap();
+        assert_eq!(config.apps[0].settings["STRIPE_KEY"], "sk_test_1234");
+        assert_eq!(config.apps[0].settings["SENTRY_DSN"], "https://sentry.io/1");
+
+        // The resolved values are never written back
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(yaml.contains("file: \"./sources_test.key\""));
+        assert!(!yaml.contains("sk_test_1234"));
+        assert!(!yaml.contains("https://sentry.io/1"));
+    }
+
+    #[test]
+    fn should_keep_vault_references() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      DATABASE_URL: \"vault://secret/data/my-app#DATABASE_URL\"\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.apps[0].settings.is_empty());
+        assert_eq!(
+            config.apps[0].sources["DATABASE_URL"],
+            Source::Vault("secret/data/my-app#DATABASE_URL".to_string())
+        );
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(saved.contains("DATABASE_URL: \"vault://secret/data/my-app#DATABASE_URL\""));
+    }
+
+    #[test]
+    fn should_keep_the_directives_of_defaults() {
+        let yaml = "version: \"2\"\ndefaults:\n  STRIPE_KEY: \"vault://secret/data/shared#STRIPE_KEY\"\n  LOG_LEVEL: \"info\"\napps:\n  - name: \"my-app\"\n    settings: {}\n  - name: \"other-app\"\n    settings: {}\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        // As read from Vault by `resolve_sources`
+        config
+            .resolved_defaults
+            .insert("STRIPE_KEY".to_string(), "sk_live_1234".to_string());
+
+        let path = "tests/defaults_saved_test.yml";
+        config.save(path, true).unwrap();
+        let saved = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(saved.contains("STRIPE_KEY: \"vault://secret/data/shared#STRIPE_KEY\""));
+        assert!(!saved.contains("sk_live_1234"));
+
+        config.apps[1].unset.push("STRIPE_KEY".to_string());
+        config.apply_defaults();
+        assert_eq!(config.apps[0].settings["STRIPE_KEY"], "sk_live_1234");
+        assert_eq!(config.apps[0].settings["LOG_LEVEL"], "info");
+        assert!(!config.apps[1].settings.contains_key("STRIPE_KEY"));
+
+        // An unread default stays a source of the apps
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.apply_defaults();
+        assert_eq!(
+            config.apps[0].sources["STRIPE_KEY"],
+            Source::Vault("secret/data/shared#STRIPE_KEY".to_string())
+        );
+        assert!(!serde_yaml::to_string(&config)
+            .unwrap()
+            .contains("sk_live_1234"));
+    }
+
+    #[test]
+    fn should_replace_sources_when_merging() {
+        let mut app = App::new();
+        app.sources
+            .insert("TOKEN".to_string(), Source::Env("TOKEN".to_string()));
+        let mut other = App::new();
+        other
+            .settings
+            .insert("TOKEN".to_string(), "secret".to_string());
+        app.merge(other);
+        assert!(app.sources.is_empty());
+        assert_eq!(app.settings["TOKEN"], "secret");
+    }
+
+    #[test]
+    fn should_not_overwrite_existing_file() {
+        let mut config = Config::new();
+        let result = config.save("tests/config_test.yml", false);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "tests/config_test.yml already exists, use --force to overwrite it"
+        );
+        assert_eq!(
+            Config::from_path(String::from("tests/config_test.yml"))
+                .unwrap()
+                .apps
+                .len(),
+            2
+        );
+
+        let result = Config::migrate(
+            String::from("tests/config_test.yml"),
+            "tests/config_v2_test.yml",
+            false,
+        );
+        assert!(result.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_save_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = "tests/private_test.yml";
+        let mut config = Config::new();
+        config.save(path, false).unwrap();
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_serialize_sorted_settings() {
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
+        settings.insert("ZOO".to_string(), "1".to_string());
+        settings.insert("BAR".to_string(), "2".to_string());
+        settings.insert("FOO".to_string(), "3".to_string());
+        let config = Config::from_apps(&["my-fuzzy-app".to_string()], settings).unwrap();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        let bar = yaml.find("BAR").unwrap();
+        let foo = yaml.find("FOO").unwrap();
+        let zoo = yaml.find("ZOO").unwrap();
+        assert!(bar < foo && foo < zoo);
+    }
+
     #[test]
     fn should_remove_file_on_disk() {
         // Remove the generated test file
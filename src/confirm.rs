@@ -0,0 +1,91 @@
+//! Ask the user to confirm a command on the terminal
+//!
+//! # Example
+//!
+//! ```rust
+//! if confirm::is_interactive() {
+//!     let answer = confirm::ask("Push these changes? [y/N] ")?;
+//!     if confirm::is_yes(&answer) {
+//!         // ...
+//!     }
+//! }
+//! ```
+use atty::{self, Stream};
+use std::io::{self, BufRead, Write};
+
+/// Answers to the confirmation of a change of several apps
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    /// Confirm the change of every app
+    All,
+    /// Confirm the change of no app
+    Nothing,
+    /// Confirm the change of each app separately
+    Select,
+}
+
+impl Answer {
+    /// Read the answer to the confirmation of a change of several apps
+    ///
+    /// # Arguments
+    /// * `answer` - The answer of the user, nothing being confirmed by default
+    ///
+    pub fn from_answer(answer: &str) -> Self {
+        match answer.trim().to_lowercase().as_str() {
+            "s" | "select" => Answer::Select,
+            answer if is_yes(answer) => Answer::All,
+            _ => Answer::Nothing,
+        }
+    }
+}
+
+/// Check if the user can answer questions, the standard input being a terminal
+pub fn is_interactive() -> bool {
+    atty::is(Stream::Stdin)
+}
+
+/// Check if an answer confirms a question
+///
+/// # Arguments
+/// * `answer` - The answer of the user, nothing being confirmed by default
+///
+pub fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    answer == "y" || answer == "yes"
+}
+
+/// Ask a question on the standard error output and read the answer from the standard input
+///
+/// # Arguments
+/// * `question` - The question, printed without a new line
+///
+/// # Result
+/// * `Result<String, io::Error>` - The answer, empty if the standard input is closed
+///
+pub fn ask(question: &str) -> Result<String, io::Error> {
+    let mut stderr = io::stderr();
+    write!(stderr, "{}", question)?;
+    stderr.flush()?;
+
+    let mut answer = String::new();
+    let stdin = io::stdin();
+    stdin.lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_answers() {
+        assert!(is_yes("y"));
+        assert!(is_yes(" Yes\n"));
+        assert!(!is_yes(""));
+        assert!(!is_yes("no"));
+        assert_eq!(Answer::from_answer("Y"), Answer::All);
+        assert_eq!(Answer::from_answer("s"), Answer::Select);
+        assert_eq!(Answer::from_answer(""), Answer::Nothing);
+        assert_eq!(Answer::from_answer("maybe"), Answer::Nothing);
+    }
+}
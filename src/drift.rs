@@ -0,0 +1,140 @@
+//! Detect the config vars of apps changed on heroku outside of their config file
+//!
+//! # Example
+//!
+//! ```rust
+//! let keys = drift::classify(&app, &remote_config_vars);
+//! let drifted = keys.iter().any(|key| key.state.is_drift());
+//! ```
+use config::App;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    /// The config var has the same value on heroku and in the config file
+    InSync,
+    /// The config var has another value on heroku, or is set on heroku while it should be removed
+    Drifted,
+    /// The config var is only set on heroku
+    RemoteOnly,
+    /// The config var is only set in the config file
+    LocalOnly,
+}
+
+impl State {
+    /// Check if the config var differs from the config file
+    ///
+    /// Remote-only config vars are not a drift, they are only reported: add-ons set their own
+    /// config vars (ex: DATABASE_URL), and config files do not have to manage every config var.
+    pub fn is_drift(self) -> bool {
+        self == State::Drifted || self == State::LocalOnly
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            State::InSync => "in-sync",
+            State::Drifted => "drifted",
+            State::RemoteOnly => "remote-only",
+            State::LocalOnly => "local-only",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct KeyState {
+    /// The key of the config var
+    pub key: String,
+    /// The state of the config var on heroku
+    pub state: State,
+}
+
+/// Compare the config vars of an app on heroku with its config file
+///
+/// # Arguments
+/// * `app` - The app in the config file, with the defaults applied
+/// * `remote` - The config vars of the app on heroku
+///
+/// # Result
+/// * `Vec<KeyState>` - The state of every config var, sorted by key
+///
+pub fn classify(app: &App, remote: &BTreeMap<String, String>) -> Vec<KeyState> {
+    let mut states: BTreeMap<&str, State> = BTreeMap::new();
+    for (key, value) in remote {
+        let state = match app.settings.get(key) {
+            Some(local) if local == value => State::InSync,
+            Some(_) => State::Drifted,
+            None if app.unset.contains(key) => State::Drifted,
+            None => State::RemoteOnly,
+        };
+        states.insert(key, state);
+    }
+    for key in app.settings.keys() {
+        if !remote.contains_key(key) {
+            states.insert(key, State::LocalOnly);
+        }
+    }
+    for key in &app.unset {
+        if !remote.contains_key(key) {
+            states.insert(key, State::InSync);
+        }
+    }
+    states
+        .into_iter()
+        .map(|(key, state)| KeyState {
+            key: key.to_string(),
+            state,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(key: &str, state: State) -> KeyState {
+        KeyState {
+            key: key.to_string(),
+            state,
+        }
+    }
+
+    #[test]
+    fn should_classify_config_vars() {
+        let mut app = App::new();
+        app.settings
+            .insert("ENV".to_string(), "production".to_string());
+        app.settings.insert("PORT".to_string(), "5000".to_string());
+        app.settings.insert("NEW_VAR".to_string(), "1".to_string());
+        app.unset = vec!["OLD_VAR".to_string(), "GONE_VAR".to_string()];
+        let mut remote: BTreeMap<String, String> = BTreeMap::new();
+        remote.insert("ENV".to_string(), "staging".to_string());
+        remote.insert("PORT".to_string(), "5000".to_string());
+        remote.insert("OLD_VAR".to_string(), "1".to_string());
+        remote.insert("SENTRY_DSN".to_string(), "x".to_string());
+
+        assert_eq!(
+            classify(&app, &remote),
+            vec![
+                state("ENV", State::Drifted),
+                state("GONE_VAR", State::InSync),
+                state("NEW_VAR", State::LocalOnly),
+                state("OLD_VAR", State::Drifted),
+                state("PORT", State::InSync),
+                state("SENTRY_DSN", State::RemoteOnly),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_only_fail_on_drifts() {
+        assert!(State::Drifted.is_drift());
+        assert!(State::LocalOnly.is_drift());
+        assert!(!State::RemoteOnly.is_drift());
+        assert!(!State::InSync.is_drift());
+    }
+}
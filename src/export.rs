@@ -0,0 +1,209 @@
+//! Render the settings of an app into the formats of other deployment platforms
+//!
+//! # Example
+//!
+//! ```rust
+//! let content = export::render(export::Format::K8sSecret, "my-app", &app.settings)?;
+//! let file_name = export::file_name(export::Format::K8sSecret, "my-app");
+//! ```
+use base64;
+use serde_json;
+use std::collections::BTreeMap;
+
+/// Formats of the exported files
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// A Kubernetes Secret, with the values encoded in base64
+    K8sSecret,
+    /// A Kubernetes ConfigMap
+    K8sConfigMap,
+    /// A file for the `--env-file` option of docker
+    DockerEnv,
+    /// A file for the `EnvironmentFile` option of systemd units
+    SystemdEnv,
+    /// A terraform variables file, with a `config_vars` map
+    TerraformTfvars,
+}
+
+impl Format {
+    /// Names of the formats, as given on the command line
+    pub const NAMES: &'static [&'static str] = &[
+        "k8s-secret",
+        "k8s-configmap",
+        "docker-env",
+        "systemd-env",
+        "terraform-tfvars",
+    ];
+
+    /// Read a format from its name, a Kubernetes Secret by default
+    ///
+    /// # Arguments
+    /// * `name` - The name of the format
+    ///
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "k8s-configmap" => Format::K8sConfigMap,
+            "docker-env" => Format::DockerEnv,
+            "systemd-env" => Format::SystemdEnv,
+            "terraform-tfvars" => Format::TerraformTfvars,
+            _ => Format::K8sSecret,
+        }
+    }
+}
+
+/// Name of the file exported for an app
+///
+/// # Arguments
+/// * `format` - The format of the file
+/// * `app_name` - The name of the app
+///
+pub fn file_name(format: Format, app_name: &str) -> String {
+    let extension = match format {
+        Format::K8sSecret | Format::K8sConfigMap => "yml",
+        Format::DockerEnv | Format::SystemdEnv => "env",
+        Format::TerraformTfvars => "tfvars",
+    };
+    format!("{}.{}", app_name, extension)
+}
+
+/// Render the settings of an app
+///
+/// # Arguments
+/// * `format` - The format of the file
+/// * `app_name` - The name of the app, used as the name of Kubernetes resources
+/// * `settings` - The config vars of the app
+///
+/// # Result
+/// * `Result<String, String>` - The content of the file, or an Error message if a value can not
+///   be represented in the format
+///
+pub fn render(
+    format: Format,
+    app_name: &str,
+    settings: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    let mut content = String::new();
+    match format {
+        Format::K8sSecret | Format::K8sConfigMap => {
+            let kind = match format {
+                Format::K8sSecret => "Secret",
+                _ => "ConfigMap",
+            };
+            content.push_str(&format!(
+                "apiVersion: v1\nkind: {}\nmetadata:\n  name: {}\n",
+                kind, app_name
+            ));
+            if format == Format::K8sSecret {
+                content.push_str("type: Opaque\n");
+            }
+            content.push_str(if settings.is_empty() {
+                "data: {}\n"
+            } else {
+                "data:\n"
+            });
+            for (key, value) in settings {
+                let value = match format {
+                    Format::K8sSecret => base64::encode(value.as_bytes()),
+                    _ => quote(value),
+                };
+                content.push_str(&format!("  {}: {}\n", key, value));
+            }
+        }
+        Format::DockerEnv => {
+            for (key, value) in settings {
+                check_single_line(key, value, "docker-env")?;
+                content.push_str(&format!("{}={}\n", key, value));
+            }
+        }
+        Format::SystemdEnv => {
+            for (key, value) in settings {
+                check_single_line(key, value, "systemd-env")?;
+                let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+                content.push_str(&format!("{}=\"{}\"\n", key, value));
+            }
+        }
+        Format::TerraformTfvars => {
+            content.push_str("config_vars = {\n");
+            for (key, value) in settings {
+                // Terraform interpolates ${...} and %{...} sequences in strings
+                let value = quote(value).replace("${", "$${").replace("%{", "%%{");
+                content.push_str(&format!("  {} = {}\n", quote(key), value));
+            }
+            content.push_str("}\n");
+        }
+    }
+    Ok(content)
+}
+
+/// Refuse values on several lines, for the formats reading a config var per line
+fn check_single_line(key: &str, value: &str, format: &str) -> Result<(), String> {
+    if value.contains('\n') || value.contains('\r') {
+        return Err(format!(
+            "the value of {} is on several lines, it can not be exported in the {} format",
+            key, format
+        ));
+    }
+    Ok(())
+}
+
+/// Quote a string, with the escapes of JSON also understood by YAML and terraform
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> BTreeMap<String, String> {
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
+        settings.insert("ENV".to_string(), "production".to_string());
+        settings.insert("GREETING".to_string(), "say \"hi\" to ${USER}".to_string());
+        settings
+    }
+
+    #[test]
+    fn should_render_k8s_secrets() {
+        assert_eq!(
+            render(Format::K8sSecret, "my-app", &settings()).unwrap(),
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: my-app\ntype: Opaque\ndata:\n  ENV: cHJvZHVjdGlvbg==\n  GREETING: c2F5ICJoaSIgdG8gJHtVU0VSfQ==\n"
+        );
+        assert_eq!(
+            render(Format::K8sConfigMap, "my-app", &BTreeMap::new()).unwrap(),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: my-app\ndata: {}\n"
+        );
+    }
+
+    #[test]
+    fn should_render_env_files() {
+        assert_eq!(
+            render(Format::DockerEnv, "my-app", &settings()).unwrap(),
+            "ENV=production\nGREETING=say \"hi\" to ${USER}\n"
+        );
+        assert_eq!(
+            render(Format::SystemdEnv, "my-app", &settings()).unwrap(),
+            "ENV=\"production\"\nGREETING=\"say \\\"hi\\\" to ${USER}\"\n"
+        );
+
+        let mut settings = settings();
+        settings.insert("KEY".to_string(), "line 1\nline 2".to_string());
+        assert!(render(Format::DockerEnv, "my-app", &settings).is_err());
+    }
+
+    #[test]
+    fn should_render_terraform_variables() {
+        assert_eq!(
+            render(Format::TerraformTfvars, "my-app", &settings()).unwrap(),
+            "config_vars = {\n  \"ENV\" = \"production\"\n  \"GREETING\" = \"say \\\"hi\\\" to $${USER}\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn should_name_files() {
+        assert_eq!(file_name(Format::K8sSecret, "my-app"), "my-app.yml");
+        assert_eq!(
+            file_name(Format::TerraformTfvars, "my-app"),
+            "my-app.tfvars"
+        );
+    }
+}
@@ -1,20 +1,34 @@
 //! Main interface to interact with the heroku API
 //!
-//! This library is only allowing push of config vars to this day.
+//! This library allows to push and pull config vars, and to list the apps available to a user.
 //!
+//! # Example
+//!
+//! ```rust,no_run
+//! use heroku_env::heroku::PlatformAPI;
+//!
+//! let mut client = PlatformAPI::new("my-heroku-api-token".to_string());
+//! match client.get_config_vars("my-app".to_string()) {
+//!     Ok(config_vars) => println!("{}", config_vars.join("\n")),
+//!     Err(err) => eprintln!("{}: {}", err.id, err.message),
+//! }
+//! ```
+use api;
 use reqwest;
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
-use serde_json;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
+/// Client of the Heroku Platform API, authenticated with an API token
 pub struct PlatformAPI {
     /// The reqwest client
     client: reqwest::Client,
     /// The token to use with the heroku API
     token: String,
+    /// The URL of the heroku API, a local server in tests
+    base_url: String,
 }
 
+/// Error returned by the Platform API
 #[derive(Debug, Deserialize)]
 pub struct PlatformError {
     /// Id of the error message
@@ -23,6 +37,77 @@ pub struct PlatformError {
     pub message: String,
 }
 
+/// Attachment of an add-on to an app
+#[derive(Debug, Deserialize)]
+pub struct AddonAttachment {
+    /// Name of the attachment, used as prefix of the config vars it sets (ex: DATABASE)
+    pub name: String,
+    /// The add-on behind the attachment
+    pub addon: Addon,
+}
+
+/// An add-on, such as a database
+#[derive(Debug, Deserialize)]
+pub struct Addon {
+    /// Name of the add-on (ex: postgresql-curly-12345)
+    pub name: String,
+}
+
+/// Release of an app, created by every change of its code or config vars
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    /// Unique identifier of the release
+    pub id: String,
+    /// Version of the release, incremented for every release of an app
+    pub version: u64,
+    /// Description of the release (ex: "Set MY_VAR config vars")
+    pub description: String,
+    /// Status of the release: "pending", "succeeded" or "failed"
+    pub status: String,
+    /// Date of creation of the release
+    pub created_at: String,
+    /// The user who created the release
+    pub user: Option<ReleaseUser>,
+}
+
+/// User who created a release
+#[derive(Debug, Deserialize)]
+pub struct ReleaseUser {
+    /// Email address of the user
+    pub email: String,
+}
+
+impl Release {
+    /// Check if the release is still running (release phase, restart of the dynos)
+    pub fn is_pending(&self) -> bool {
+        self.status == "pending"
+    }
+
+    /// Check if the release has failed
+    pub fn is_failed(&self) -> bool {
+        self.status == "failed"
+    }
+}
+
+impl AddonAttachment {
+    /// Check if a config var is managed by the add-on of this attachment
+    ///
+    /// Add-ons set their config vars with the name of the attachment as prefix, an attachment
+    /// named `DATABASE` manages `DATABASE_URL` for instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of a config var
+    ///
+    /// # Result
+    ///
+    /// * `bool` - true if the config var belongs to the add-on
+    ///
+    pub fn manages(&self, key: &str) -> bool {
+        key == self.name || key.starts_with(&format!("{}_", self.name))
+    }
+}
+
 impl PlatformAPI {
     /// Constructor of the Platform API
     ///
@@ -35,12 +120,18 @@ impl PlatformAPI {
     /// # Example
     ///
     /// ```rust
-    /// let mut client = heroku::PlatformAPI::new("1234");
+    /// use heroku_env::heroku::PlatformAPI;
+    ///
+    /// let mut client = PlatformAPI::new("1234".to_string());
     /// ```
     ///
     pub fn new(token: String) -> PlatformAPI {
         let client = reqwest::Client::new();
-        PlatformAPI { client, token }
+        PlatformAPI {
+            client,
+            token,
+            base_url: api::BASE_URL.to_string(),
+        }
     }
 
     /// Set config vars on heroku
@@ -48,7 +139,7 @@ impl PlatformAPI {
     /// # Arguments
     ///
     /// * `app_name` - A string containing the app to push config vars
-    /// * `configs` - A HashMap containing key-value pairs
+    /// * `configs` - A BTreeMap containing key-value pairs
     ///
     /// # Result
     ///
@@ -56,10 +147,12 @@ impl PlatformAPI {
     ///
     /// # Example
     ///
-    /// ```rust
-    /// let mut client = heroku::PlatformAPI::new("1234");
-    /// let settings = HashMap::new();
-    /// settings.push("MY_VAR", "my value");
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError};
+    /// # use std::collections::BTreeMap;
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let mut settings = BTreeMap::new();
+    /// settings.insert("MY_VAR".to_string(), "my value".to_string());
     /// let name = String::from("my-app");
     /// let result: Result<Vec<String>, PlatformError> = client.set_config_vars(name, settings);
     /// assert!(result.is_ok());
@@ -67,26 +160,48 @@ impl PlatformAPI {
     pub fn set_config_vars(
         &mut self,
         app_name: String,
-        configs: HashMap<String, String>,
+        configs: BTreeMap<String, String>,
     ) -> Result<Vec<String>, PlatformError> {
-        let url = format!("https://api.heroku.com/apps/{}/config-vars", app_name);
-
-        let mut response = self
-            .client
-            .patch(&url)
-            .headers(self.construct_headers())
-            .json(&configs)
-            .send()
-            .expect("Error: failed to set the app config vars.");
-
-        // Read the body response from the API call in raw text
-        let result = response.text().unwrap();
-
-        if !response.status().is_success() {
-            return Err(self.error_from_response(&result));
-        }
+        let changes: BTreeMap<String, Option<String>> = configs
+            .into_iter()
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        self.patch_config_vars(app_name, changes)
+    }
 
-        Ok(self.success_from_response(&result))
+    /// Set and remove config vars on heroku in a single update, creating a single release
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the app to push config vars
+    /// * `changes` - A BTreeMap containing the new values of the config vars, `None` removes
+    ///   a config var
+    ///
+    /// # Result
+    ///
+    /// * `Result<Vec<String>, PlatformError>` - Vector with strings (key=value), or PaltformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError};
+    /// # use std::collections::BTreeMap;
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let mut changes = BTreeMap::new();
+    /// changes.insert("MY_VAR".to_string(), Some("my value".to_string()));
+    /// changes.insert("MY_OLD_VAR".to_string(), None);
+    /// let name = String::from("my-app");
+    /// let result: Result<Vec<String>, PlatformError> = client.patch_config_vars(name, changes);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn patch_config_vars(
+        &mut self,
+        app_name: String,
+        configs: BTreeMap<String, Option<String>>,
+    ) -> Result<Vec<String>, PlatformError> {
+        let request = api::patch_config_vars(&self.token, &app_name, &configs);
+        let response = self.send(request, "Error: failed to set the app config vars.");
+        api::config_vars(&response)
     }
 
     /// Get config vars on heroku
@@ -101,161 +216,410 @@ impl PlatformAPI {
     ///
     /// # Example
     ///
-    /// ```rust
-    /// let mut client = heroku::PlatformAPI::new("1234");
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError};
+    /// let mut client = PlatformAPI::new("1234".to_string());
     /// let name = String::from("my-app");
     /// let result: Result<Vec<String>, PlatformError> = client.get_config_vars(name);
     /// assert!(result.is_ok());
     /// ```
     pub fn get_config_vars(&mut self, app_name: String) -> Result<Vec<String>, PlatformError> {
-        let url = format!("https://api.heroku.com/apps/{}/config-vars", app_name);
+        let request = api::get_config_vars(&self.token, &app_name);
+        let response = self.send(request, "Error: failed to get the app config vars.");
+        api::config_vars(&response)
+    }
 
-        let mut response = self
-            .client
-            .get(&url)
-            .headers(self.construct_headers())
-            .send()
-            .expect("Error: failed to get the app config vars.");
+    /// List the names of all the apps the user has access to
+    ///
+    /// # Result
+    ///
+    /// * `Result<Vec<String>, PlatformError>` - Vector with app names, or PlatformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let result: Result<Vec<String>, PlatformError> = client.list_apps();
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn list_apps(&mut self) -> Result<Vec<String>, PlatformError> {
+        let apps = self.get_all(&api::List::apps())?;
+        Ok(api::names_from_list(&apps))
+    }
 
-        // Read the body response from the API call in raw text
-        let result = response.text().unwrap();
+    /// List the names of all the apps owned by a team (or organization)
+    ///
+    /// # Arguments
+    ///
+    /// * `team_name` - A string containing the name of the team
+    ///
+    /// # Result
+    ///
+    /// * `Result<Vec<String>, PlatformError>` - Vector with app names, or PlatformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let team = String::from("my-team");
+    /// let result: Result<Vec<String>, PlatformError> = client.list_team_apps(team);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn list_team_apps(&mut self, team_name: String) -> Result<Vec<String>, PlatformError> {
+        let apps = self.get_all(&api::List::team_apps(&team_name))?;
+        Ok(api::names_from_list(&apps))
+    }
 
-        if !response.status().is_success() {
-            return Err(self.error_from_response(&result));
-        }
+    /// Get the latest release of an app
+    ///
+    /// Every update of the config vars creates a new release of the app, and restarts its dynos.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Result<Release, PlatformError>` - The latest release, or PlatformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError, Release};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let name = String::from("my-app");
+    /// let result: Result<Release, PlatformError> = client.get_latest_release(name);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn get_latest_release(&mut self, app_name: String) -> Result<Release, PlatformError> {
+        let list = api::List::latest_release(&app_name);
+        let (releases, _) = self.get_page(&list.path, list.range)?;
+        api::latest_release(&app_name, releases)
+    }
 
-        Ok(self.success_from_response(&result))
+    /// List the latest releases of an app, from the latest to the oldest
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Result<Vec<Release>, PlatformError>` - Vector of releases, or PlatformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError, Release};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let name = String::from("my-app");
+    /// let result: Result<Vec<Release>, PlatformError> = client.get_latest_releases(name);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn get_latest_releases(&mut self, app_name: String) -> Result<Vec<Release>, PlatformError> {
+        let list = api::List::latest_releases(&app_name);
+        let (releases, _) = self.get_page(&list.path, list.range)?;
+        Ok(api::releases_from_list(releases))
     }
 
-    /// Map a successful response from the heroku API to a Vector
+    /// List every release of an app, from the latest to the oldest
     ///
     /// # Arguments
     ///
-    /// * `response` - JSON as text response from an API call
+    /// * `app_name` - A string containing the name of the app
     ///
-    /// # Result
+    /// # Result
+    ///
+    /// * `Result<Vec<Release>, PlatformError>` - Vector of releases, or PlatformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError, Release};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let name = String::from("my-app");
+    /// let result: Result<Vec<Release>, PlatformError> = client.get_releases(name);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn get_releases(&mut self, app_name: String) -> Result<Vec<Release>, PlatformError> {
+        let releases = self.get_all(&api::List::releases(&app_name))?;
+        Ok(api::releases_from_list(releases))
+    }
+
+    /// Get a release of an app
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    /// * `release` - The id or the version of the release
+    ///
+    /// # Result
+    ///
+    /// * `Result<Release, PlatformError>` - The release, or PlatformError struct
     ///
-    /// * `Vec<String>` - A vector containing strings, formated as "key=value"
+    /// # Example
     ///
-    fn success_from_response(&mut self, response: &str) -> Vec<String> {
-        // Parse the string response to JSON
-        let serde_value: Value = serde_json::from_str(&response).unwrap();
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{PlatformAPI, PlatformError, Release};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let name = String::from("my-app");
+    /// let result: Result<Release, PlatformError> = client.get_release(name, "42".to_string());
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn get_release(
+        &mut self,
+        app_name: String,
+        release: String,
+    ) -> Result<Release, PlatformError> {
+        let request = api::get_release(&self.token, &app_name, &release);
+        let response = self.send(request, "Error: failed to get the app release.");
+        api::release(&response)
+    }
 
-        // Use this JSON as object to iterate on it
-        let config_vars = serde_value.as_object().unwrap();
+    /// List the add-on attachments of an app
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Result<Vec<AddonAttachment>, PlatformError>` - Vector of attachments, or PlatformError struct
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use heroku_env::heroku::{AddonAttachment, PlatformAPI, PlatformError};
+    /// let mut client = PlatformAPI::new("1234".to_string());
+    /// let name = String::from("my-app");
+    /// let result: Result<Vec<AddonAttachment>, PlatformError> = client.get_addon_attachments(name);
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn get_addon_attachments(
+        &mut self,
+        app_name: String,
+    ) -> Result<Vec<AddonAttachment>, PlatformError> {
+        let attachments = self.get_all(&api::List::addon_attachments(&app_name))?;
+        api::attachments_from_list(attachments)
+    }
 
+    /// Fetch every page of a list endpoint of the heroku API
+    ///
+    /// # Arguments
+    ///
+    /// * `list` - The list endpoint
+    ///
+    /// # Result
+    ///
+    /// * `Result<Vec<Value>, PlatformError>` - Every record of the list, or PlatformError struct
+    ///
+    fn get_all(&mut self, list: &api::List) -> Result<Vec<Value>, PlatformError> {
         let mut records = Vec::new();
+        let mut next_range = Some(list.range.to_string());
 
-        // Iterate over the config vars and put the result in a Vector
-        for key in config_vars.keys() {
-            let result: String = format!(
-                "{}={}",
-                key.to_string(),
-                config_vars.get(key).unwrap().as_str().unwrap()
-            );
-            records.push(result);
+        while let Some(range) = next_range {
+            let (page, next) = self.get_page(&list.path, &range)?;
+            records.extend(page);
+            next_range = next;
         }
-        records
+        Ok(records)
     }
 
-    /// Map an error from an HTTP call into a PlatformError struct
+    /// Fetch a single page of a list endpoint of the heroku API
     ///
-    /// # Argruments
+    /// The platform API returns lists in pages, the next page being requested by sending back
+    /// the `Next-Range` header of a `206 Partial Content` response.
     ///
-    /// * `response` - JSON as text response from an API call
+    /// # Arguments
     ///
-    /// # Result
+    /// * `path` - The path of the list endpoint
+    /// * `range` - The `Range` header of the page (ex: "version ..; order=desc, max=1")
+    ///
+    /// # Result
     ///
-    /// * `PlatformError` - A struct containing the id of the error message, and the text message
+    /// * `Result<(Vec<Value>, Option<String>), PlatformError>` - The records of the page and
+    ///   the range of the next page if any, or PlatformError struct
     ///
-    fn error_from_response(&mut self, response: &str) -> PlatformError {
-        // Parse the string response to JSON and Deserialize in a PlatformError struct
-        let platform_error: PlatformError = serde_json::from_str(&response).unwrap();
-        platform_error
+    fn get_page(
+        &mut self,
+        path: &str,
+        range: &str,
+    ) -> Result<(Vec<Value>, Option<String>), PlatformError> {
+        let request = api::get_page(&self.token, path, range);
+        let response = self.send(request, "Error: failed to list the resources.");
+        api::page(&response)
     }
 
-    /// Construct the necessary headers for HTTP request to heroku platform API
+    /// Send a request to the heroku API and read its response
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to send
+    /// * `failure` - The message of the panic if the API cannot be reached
     ///
     /// # Result
     ///
-    /// * `HeaderMap` - A Headers Map containing HTTP headers (see reqwest documentation)
+    /// * `api::Response` - The status, the next range and the body of the response
     ///
-    fn construct_headers(&mut self) -> HeaderMap {
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            ACCEPT,
-            HeaderValue::from_static("application/vnd.heroku+json; version=3"),
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        let access_token = format!("Bearer {}", self.token);
-        headers.insert(AUTHORIZATION, HeaderValue::from_str(&access_token).unwrap());
-        headers
+    fn send(&mut self, request: api::Request, failure: &str) -> api::Response {
+        let mut builder = self
+            .client
+            .request(
+                request.method,
+                &format!("{}{}", self.base_url, request.path),
+            )
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        let mut response = builder.send().expect(failure);
+
+        // Read the body response from the API call in raw text
+        let body = response.text().unwrap();
+
+        api::Response {
+            status: response.status(),
+            next_range: api::next_range(response.headers()),
+            body,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve a single response on a local port, returning a client of its address
+    ///
+    /// The response is only sent to a request starting with `expected` (ex: the method and the
+    /// path) with the API token, the other requests get a 400 Bad Request.
+    fn stub_server(
+        expected: &'static str,
+        status: &'static str,
+        body: &'static str,
+    ) -> PlatformAPI {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0; 4096];
+                let size = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..size]).to_string();
+                let (status, body) = if request.starts_with(expected)
+                    && request
+                        .to_lowercase()
+                        .contains("authorization: bearer 1234")
+                {
+                    (status, body)
+                } else {
+                    (
+                        "400 Bad Request",
+                        "{\"id\":\"bad_request\",\"message\":\"Unexpected request.\"}",
+                    )
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        let mut client = PlatformAPI::new("1234".to_string());
+        client.base_url = address;
+        client
+    }
+
+    /// Serve an error of authentication to a request starting with `expected`
+    fn unauthorized(expected: &'static str) -> PlatformAPI {
+        stub_server(
+            expected,
+            "401 Unauthorized",
+            "{\"id\":\"unauthorized\",\"message\":\"Invalid credentials provided.\"}",
+        )
+    }
 
     #[test]
     fn has_token() {
         let token = String::from("1234");
         let client_test = PlatformAPI::new(token);
         assert_eq!(client_test.token, "1234");
-    }
-
-    #[test]
-    fn has_headers() {
-        let token = String::from("1234");
-        let mut client_test = PlatformAPI::new(token);
-        let headers = client_test.construct_headers();
-
-        assert!(headers.contains_key(ACCEPT));
-        assert!(headers.contains_key(AUTHORIZATION));
-        assert!(headers.contains_key(CONTENT_TYPE));
-
-        assert_eq!(headers[ACCEPT], "application/vnd.heroku+json; version=3");
-        assert_eq!(headers[CONTENT_TYPE], "application/json");
-        assert_eq!(headers[AUTHORIZATION], "Bearer 1234");
+        assert_eq!(client_test.base_url, "https://api.heroku.com");
     }
 
     #[test]
     fn should_fail_to_update_config_vars() {
-        let token = String::from("1234");
-        let mut client = PlatformAPI::new(token);
+        let mut client = unauthorized("PATCH /apps/fuzzy-app/config-vars ");
         let app_name = "fuzzy-app".to_string();
-        let mut settings: HashMap<String, String> = HashMap::new();
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
         settings.insert("key".to_string(), "value".to_string());
 
         let result = client.set_config_vars(app_name, settings);
-        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().id, "unauthorized");
+    }
+
+    #[test]
+    fn should_get_config_vars() {
+        let mut client = stub_server(
+            "GET /apps/fuzzy-app/config-vars ",
+            "200 OK",
+            "{\"PORT\":\"8080\",\"ENV\":\"production\"}",
+        );
+        let result = client.get_config_vars("fuzzy-app".to_string());
+        assert_eq!(result.unwrap(), vec!["ENV=production", "PORT=8080"]);
     }
 
     #[test]
     fn should_fail_to_get_config_vars() {
-        let token = String::from("1234");
-        let mut client = PlatformAPI::new(token);
+        let mut client = unauthorized("GET /apps/fuzzy-app/config-vars ");
         let app_name = "fuzzy-app".to_string();
         let result = client.get_config_vars(app_name);
-        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().id, "unauthorized");
     }
 
     #[test]
-    fn should_map_config_vars_as_vec() {
-        let token = String::from("1234");
-        let mut client = PlatformAPI::new(token);
-        let http_response = "{ \"TEST\": \"VALUE\" }".to_string();
-        let v: Vec<String> = client.success_from_response(&http_response);
-        assert_eq!(v[0], "TEST=VALUE");
+    fn should_fail_to_list_apps() {
+        let mut client = unauthorized("GET /teams/fuzzy-team/apps ");
+        let result = client.list_team_apps("fuzzy-team".to_string());
+        assert_eq!(result.unwrap_err().id, "unauthorized");
     }
 
     #[test]
-    fn should_map_error_response() {
-        let token = String::from("1234");
-        let mut client = PlatformAPI::new(token);
-        let http_response = "{ \"id\": \"Bad\", \"message\": \"This is bad\" }".to_string();
-        let err: PlatformError = client.error_from_response(&http_response);
-        assert_eq!(err.id, "Bad");
-        assert_eq!(err.message, "This is bad");
+    fn should_fail_to_get_addon_attachments() {
+        let mut client = unauthorized("GET /apps/fuzzy-app/addon-attachments ");
+        let result = client.get_addon_attachments("fuzzy-app".to_string());
+        assert_eq!(result.unwrap_err().id, "unauthorized");
+    }
+
+    #[test]
+    fn should_detect_addon_config_vars() {
+        let http_response =
+            "{ \"name\": \"DATABASE\", \"addon\": { \"name\": \"postgresql-curly-1234\" } }";
+        let attachment: AddonAttachment = serde_json::from_str(http_response).unwrap();
+        assert_eq!(attachment.addon.name, "postgresql-curly-1234");
+        assert!(attachment.manages("DATABASE_URL"));
+        assert!(!attachment.manages("MY_DATABASE_URL"));
+        assert!(!attachment.manages("DATABASES"));
+    }
+
+    #[test]
+    fn should_fail_to_get_latest_release() {
+        let mut client = unauthorized("GET /apps/fuzzy-app/releases ");
+        let result = client.get_latest_release("fuzzy-app".to_string());
+        assert_eq!(result.unwrap_err().id, "unauthorized");
+    }
+
+    #[test]
+    fn should_fail_to_get_releases() {
+        let mut client = unauthorized("GET /apps/fuzzy-app/releases ");
+        let result = client.get_releases("fuzzy-app".to_string());
+        assert_eq!(result.unwrap_err().id, "unauthorized");
     }
 }
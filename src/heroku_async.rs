@@ -0,0 +1,496 @@
+//! Async client of the heroku API, for programs running on tokio
+//!
+//! It has the same operations as the blocking `heroku::PlatformAPI`, each one returning a future
+//! (futures 0.1) to run on the tokio runtime of the program. It requires the `async` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! extern crate futures;
+//! extern crate heroku_env;
+//!
+//! use futures::Future;
+//! use heroku_env::heroku_async::PlatformAPI;
+//!
+//! fn print_config_vars(client: &PlatformAPI) -> impl Future<Item = (), Error = ()> {
+//!     client
+//!         .get_config_vars("my-app".to_string())
+//!         .map(|config_vars| println!("{}", config_vars.join("\n")))
+//!         .map_err(|err| eprintln!("{}: {}", err.id, err.message))
+//! }
+//!
+//! fn main() {
+//!     let client = PlatformAPI::new("my-heroku-api-token".to_string());
+//!     // Run on the runtime of the program, ex: tokio::run(print_config_vars(&client))
+//! }
+//! ```
+use api;
+use futures::future::{self, Loop};
+use futures::Future;
+use heroku::{AddonAttachment, PlatformError, Release};
+use reqwest;
+use reqwest::r#async::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Async client of the Heroku Platform API, authenticated with an API token
+///
+/// Cloning the client is cheap, the clones share the same pool of connections.
+#[derive(Clone)]
+pub struct PlatformAPI {
+    /// The async reqwest client
+    client: Client,
+    /// The token to use with the heroku API
+    token: String,
+    /// The URL of the heroku API, a local server in tests
+    base_url: String,
+}
+
+impl PlatformAPI {
+    /// Constructor of the async Platform API
+    ///
+    /// # Arguments
+    /// * `token` - A String containing the heroku API token
+    ///
+    /// # Result
+    /// * `PlatformAPI` - A PlatformAPI structure
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use heroku_env::heroku_async::PlatformAPI;
+    ///
+    /// let client = PlatformAPI::new("1234".to_string());
+    /// ```
+    ///
+    pub fn new(token: String) -> PlatformAPI {
+        let client = Client::new();
+        PlatformAPI {
+            client,
+            token,
+            base_url: api::BASE_URL.to_string(),
+        }
+    }
+
+    /// Set config vars on heroku
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the app to push config vars
+    /// * `configs` - A BTreeMap containing key-value pairs
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<String>, Error = PlatformError>` - Vector with strings (key=value),
+    ///   or PlatformError struct
+    ///
+    pub fn set_config_vars(
+        &self,
+        app_name: String,
+        configs: BTreeMap<String, String>,
+    ) -> impl Future<Item = Vec<String>, Error = PlatformError> {
+        let changes: BTreeMap<String, Option<String>> = configs
+            .into_iter()
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        self.patch_config_vars(app_name, changes)
+    }
+
+    /// Set and remove config vars on heroku in a single update, creating a single release
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the app to push config vars
+    /// * `changes` - A BTreeMap containing the new values of the config vars, `None` removes
+    ///   a config var
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<String>, Error = PlatformError>` - Vector with strings (key=value),
+    ///   or PlatformError struct
+    ///
+    pub fn patch_config_vars(
+        &self,
+        app_name: String,
+        configs: BTreeMap<String, Option<String>>,
+    ) -> impl Future<Item = Vec<String>, Error = PlatformError> {
+        self.send(api::patch_config_vars(&self.token, &app_name, &configs))
+            .and_then(|response| api::config_vars(&response))
+    }
+
+    /// Get config vars on heroku
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the app to push config vars
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<String>, Error = PlatformError>` - Vector with strings (key=value),
+    ///   or PlatformError struct
+    ///
+    pub fn get_config_vars(
+        &self,
+        app_name: String,
+    ) -> impl Future<Item = Vec<String>, Error = PlatformError> {
+        self.send(api::get_config_vars(&self.token, &app_name))
+            .and_then(|response| api::config_vars(&response))
+    }
+
+    /// List the names of all the apps the user has access to
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<String>, Error = PlatformError>` - Vector with app names,
+    ///   or PlatformError struct
+    ///
+    pub fn list_apps(&self) -> impl Future<Item = Vec<String>, Error = PlatformError> {
+        self.get_all(api::List::apps())
+            .map(|apps| api::names_from_list(&apps))
+    }
+
+    /// List the names of all the apps owned by a team (or organization)
+    ///
+    /// # Arguments
+    ///
+    /// * `team_name` - A string containing the name of the team
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<String>, Error = PlatformError>` - Vector with app names,
+    ///   or PlatformError struct
+    ///
+    pub fn list_team_apps(
+        &self,
+        team_name: String,
+    ) -> impl Future<Item = Vec<String>, Error = PlatformError> {
+        self.get_all(api::List::team_apps(&team_name))
+            .map(|apps| api::names_from_list(&apps))
+    }
+
+    /// Get the latest release of an app
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Release, Error = PlatformError>` - The latest release,
+    ///   or PlatformError struct
+    ///
+    pub fn get_latest_release(
+        &self,
+        app_name: String,
+    ) -> impl Future<Item = Release, Error = PlatformError> {
+        let list = api::List::latest_release(&app_name);
+        self.get_page(&list.path, list.range)
+            .and_then(move |(releases, _)| api::latest_release(&app_name, releases))
+    }
+
+    /// List the latest releases of an app, from the latest to the oldest
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<Release>, Error = PlatformError>` - Vector of releases,
+    ///   or PlatformError struct
+    ///
+    pub fn get_latest_releases(
+        &self,
+        app_name: String,
+    ) -> impl Future<Item = Vec<Release>, Error = PlatformError> {
+        let list = api::List::latest_releases(&app_name);
+        self.get_page(&list.path, list.range)
+            .map(|(releases, _)| api::releases_from_list(releases))
+    }
+
+    /// List every release of an app, from the latest to the oldest
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<Release>, Error = PlatformError>` - Vector of releases,
+    ///   or PlatformError struct
+    ///
+    pub fn get_releases(
+        &self,
+        app_name: String,
+    ) -> impl Future<Item = Vec<Release>, Error = PlatformError> {
+        self.get_all(api::List::releases(&app_name))
+            .map(api::releases_from_list)
+    }
+
+    /// Get a release of an app
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    /// * `release` - The id or the version of the release
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Release, Error = PlatformError>` - The release, or PlatformError struct
+    ///
+    pub fn get_release(
+        &self,
+        app_name: String,
+        release: String,
+    ) -> impl Future<Item = Release, Error = PlatformError> {
+        self.send(api::get_release(&self.token, &app_name, &release))
+            .and_then(|response| api::release(&response))
+    }
+
+    /// List the add-on attachments of an app
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - A string containing the name of the app
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<AddonAttachment>, Error = PlatformError>` - Vector of attachments,
+    ///   or PlatformError struct
+    ///
+    pub fn get_addon_attachments(
+        &self,
+        app_name: String,
+    ) -> impl Future<Item = Vec<AddonAttachment>, Error = PlatformError> {
+        self.get_all(api::List::addon_attachments(&app_name))
+            .and_then(api::attachments_from_list)
+    }
+
+    /// Fetch every page of a list endpoint of the heroku API, one after the other
+    ///
+    /// # Arguments
+    ///
+    /// * `list` - The list endpoint
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = Vec<Value>, Error = PlatformError>` - Every record of the list,
+    ///   or PlatformError struct
+    ///
+    fn get_all(&self, list: api::List) -> impl Future<Item = Vec<Value>, Error = PlatformError> {
+        let client = self.clone();
+        let path = list.path;
+        future::loop_fn(
+            (Vec::new(), list.range.to_string()),
+            move |(mut records, range)| {
+                client.get_page(&path, &range).map(move |(page, next)| {
+                    records.extend(page);
+                    match next {
+                        Some(next) => Loop::Continue((records, next)),
+                        None => Loop::Break(records),
+                    }
+                })
+            },
+        )
+    }
+
+    /// Fetch a single page of a list endpoint of the heroku API
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the list endpoint
+    /// * `range` - The `Range` header of the page (ex: "version ..; order=desc, max=1")
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = (Vec<Value>, Option<String>), Error = PlatformError>` - The records of
+    ///   the page and the range of the next page if any, or PlatformError struct
+    ///
+    fn get_page(
+        &self,
+        path: &str,
+        range: &str,
+    ) -> impl Future<Item = (Vec<Value>, Option<String>), Error = PlatformError> {
+        self.send(api::get_page(&self.token, path, range))
+            .and_then(|response| api::page(&response))
+    }
+
+    /// Send a request to the heroku API and read its response
+    ///
+    /// Unlike the blocking client, failing to reach the API is not a panic but a PlatformError
+    /// with the id `request_failed`, the program keeps running.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to send
+    ///
+    /// # Result
+    ///
+    /// * `Future<Item = api::Response, Error = PlatformError>` - The status, the next range and
+    ///   the body of the response, or PlatformError struct
+    ///
+    fn send(
+        &self,
+        request: api::Request,
+    ) -> impl Future<Item = api::Response, Error = PlatformError> {
+        let mut builder = self
+            .client
+            .request(
+                request.method,
+                &format!("{}{}", self.base_url, request.path),
+            )
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        builder
+            .send()
+            .and_then(|mut response| {
+                let status = response.status();
+                let next_range = api::next_range(response.headers());
+                // Read the body response from the API call in raw text
+                response.text().map(move |body| api::Response {
+                    status,
+                    next_range,
+                    body,
+                })
+            })
+            .map_err(error_from_request)
+    }
+}
+
+/// Map a failure to reach the heroku API into a PlatformError struct
+fn error_from_request(err: reqwest::Error) -> PlatformError {
+    PlatformError {
+        id: "request_failed".to_string(),
+        message: err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use tokio::runtime::Runtime;
+
+    /// A response of the stub server, to a request containing a part (ex: a header)
+    struct Stub {
+        /// Part of the request expected by the stub with the API token, it responds
+        /// 400 Bad Request otherwise
+        expected: &'static str,
+        /// The status of the response
+        status: &'static str,
+        /// The `Next-Range` header of the response, if any
+        next_range: Option<&'static str>,
+        /// The body of the response
+        body: &'static str,
+    }
+
+    /// Serve one response per connection on a local port, returning a client of its address
+    fn stub_server(stubs: Vec<Stub>) -> PlatformAPI {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stub in stubs {
+                if let Ok((mut stream, _)) = listener.accept() {
+                    let mut request = [0; 4096];
+                    let size = stream.read(&mut request).unwrap_or(0);
+                    let request = String::from_utf8_lossy(&request[..size]).to_lowercase();
+                    let status = if request.contains(&stub.expected.to_lowercase())
+                        && request.contains("authorization: bearer 1234")
+                    {
+                        stub.status
+                    } else {
+                        "400 Bad Request"
+                    };
+                    let next_range = match stub.next_range {
+                        Some(range) => format!("Next-Range: {}\r\n", range),
+                        None => String::new(),
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        next_range,
+                        stub.body.len(),
+                        stub.body
+                    );
+                    let _ = stream.write_all(response.as_bytes());
+                }
+            }
+        });
+        PlatformAPI {
+            client: Client::new(),
+            token: "1234".to_string(),
+            base_url: address,
+        }
+    }
+
+    #[test]
+    fn has_token() {
+        let token = String::from("1234");
+        let client_test = PlatformAPI::new(token);
+        assert_eq!(client_test.token, "1234");
+        assert_eq!(client_test.base_url, "https://api.heroku.com");
+    }
+
+    #[test]
+    fn should_get_config_vars() {
+        let client = stub_server(vec![Stub {
+            expected: "GET /apps/fuzzy-app/config-vars HTTP/1.1",
+            status: "200 OK",
+            next_range: None,
+            body: "{\"PORT\":\"8080\",\"ENV\":\"production\"}",
+        }]);
+        let mut runtime = Runtime::new().unwrap();
+        let config_vars = runtime
+            .block_on(client.get_config_vars("fuzzy-app".to_string()))
+            .unwrap();
+        assert_eq!(config_vars, vec!["ENV=production", "PORT=8080"]);
+
+        let client = stub_server(vec![Stub {
+            expected: "GET /apps/fuzzy-app/config-vars HTTP/1.1",
+            status: "404 Not Found",
+            next_range: None,
+            body: "{\"id\":\"not_found\",\"message\":\"Couldn't find that app.\"}",
+        }]);
+        let err = runtime
+            .block_on(client.get_config_vars("fuzzy-app".to_string()))
+            .unwrap_err();
+        assert_eq!(err.id, "not_found");
+        assert_eq!(err.message, "Couldn't find that app.");
+    }
+
+    #[test]
+    fn should_list_apps_of_every_page() {
+        let client = stub_server(vec![
+            Stub {
+                expected: "GET /apps HTTP/1.1",
+                status: "206 Partial Content",
+                next_range: Some("]app-2..; max=2"),
+                body: "[{\"name\":\"app-1\"},{\"name\":\"app-2\"}]",
+            },
+            Stub {
+                expected: "range: ]app-2..; max=2",
+                status: "200 OK",
+                next_range: None,
+                body: "[{\"name\":\"app-3\"}]",
+            },
+        ]);
+        let mut runtime = Runtime::new().unwrap();
+        let apps = runtime.block_on(client.list_apps()).unwrap();
+        assert_eq!(apps, vec!["app-1", "app-2", "app-3"]);
+    }
+
+    #[test]
+    fn should_report_unreachable_api() {
+        let mut client = PlatformAPI::new("1234".to_string());
+        client.base_url = "http://127.0.0.1:1".to_string();
+        let mut runtime = Runtime::new().unwrap();
+        let err = runtime
+            .block_on(client.get_config_vars("fuzzy-app".to_string()))
+            .unwrap_err();
+        assert_eq!(err.id, "request_failed");
+    }
+}
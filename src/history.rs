@@ -0,0 +1,144 @@
+//! Find the changes of config vars in the releases of an app
+//!
+//! Heroku does not keep the values of the config vars of past releases, but every update of
+//! the config vars creates a release with a description listing the keys that were changed,
+//! such as "Set MY_VAR, MY_OTHER_VAR config vars" or "Remove MY_VAR config vars".
+//!
+//! # Example
+//!
+//! ```rust
+//! let changes = history::changes_from_description("Set MY_VAR config vars");
+//! assert_eq!(changes[0].action, "Set");
+//! assert_eq!(changes[0].keys, vec!["MY_VAR"]);
+//! ```
+
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    /// What the release did to the config vars (ex: "Set", "Remove")
+    pub action: String,
+    /// The keys of the config vars
+    pub keys: Vec<String>,
+}
+
+impl Change {
+    /// Check if a config var is part of the change
+    ///
+    /// # Arguments
+    /// * `key` - The key of a config var
+    ///
+    pub fn has_key(&self, key: &str) -> bool {
+        self.keys.iter().any(|k| k == key)
+    }
+}
+
+/// Extract the changes of config vars from the description of a release
+///
+/// # Arguments
+/// * `description` - The description of a release
+///
+/// # Result
+/// * `Vec<Change>` - The changes of config vars, empty if the release did not change them
+///
+pub fn changes_from_description(description: &str) -> Vec<Change> {
+    let description = description.trim();
+    let actions = if description.ends_with(" config vars") {
+        description.trim_end_matches(" config vars")
+    } else if description.ends_with(" config var") {
+        description.trim_end_matches(" config var")
+    } else {
+        return Vec::new();
+    };
+
+    let mut changes: Vec<Change> = Vec::new();
+    for action in actions.split(" and ") {
+        let mut words = action.trim().splitn(2, ' ');
+        let verb = words.next().unwrap_or("");
+        let keys: Vec<String> = words
+            .next()
+            .unwrap_or("")
+            .split(',')
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        if verb.is_empty() || keys.is_empty() {
+            continue;
+        }
+        changes.push(Change {
+            action: verb.to_string(),
+            keys,
+        });
+    }
+    changes
+}
+
+/// Check if the description of a release is an update of some of the given config vars only
+///
+/// # Arguments
+/// * `description` - The description of a release
+/// * `keys` - The keys of the updated config vars
+///
+/// # Result
+/// * `bool` - true if the release changed config vars, all of them among `keys`
+///
+pub fn is_update_of(description: &str, keys: &[String]) -> bool {
+    let changes = changes_from_description(description);
+    !changes.is_empty()
+        && changes
+            .iter()
+            .all(|change| change.keys.iter().all(|key| keys.contains(key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_set_config_vars() {
+        let changes = changes_from_description("Set MY_VAR, MY_OTHER_VAR config vars");
+        assert_eq!(
+            changes,
+            vec![Change {
+                action: "Set".to_string(),
+                keys: vec!["MY_VAR".to_string(), "MY_OTHER_VAR".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn should_find_removed_config_vars() {
+        let changes = changes_from_description("Remove MY_VAR config vars");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].action, "Remove");
+        assert!(changes[0].has_key("MY_VAR"));
+        assert!(!changes[0].has_key("MY_OTHER_VAR"));
+    }
+
+    #[test]
+    fn should_find_several_actions() {
+        let changes = changes_from_description("Set MY_VAR and Remove OLD_VAR config vars");
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].action, "Set");
+        assert_eq!(changes[1].action, "Remove");
+        assert_eq!(changes[1].keys, vec!["OLD_VAR"]);
+    }
+
+    #[test]
+    fn should_ignore_other_releases() {
+        assert!(changes_from_description("Deploy 1a2b3c4d").is_empty());
+        assert!(
+            changes_from_description("Attach DATABASE (@ref:postgresql-curly-1234)").is_empty()
+        );
+    }
+
+    #[test]
+    fn should_match_an_update_of_config_vars() {
+        let keys = vec!["MY_VAR".to_string(), "OLD_VAR".to_string()];
+        assert!(is_update_of(
+            "Set MY_VAR and Remove OLD_VAR config vars",
+            &keys
+        ));
+        assert!(is_update_of("Set MY_VAR config vars", &keys));
+        assert!(!is_update_of("Set MY_VAR, OTHER_VAR config vars", &keys));
+        assert!(!is_update_of("Deploy 1a2b3c4d", &keys));
+    }
+}
@@ -0,0 +1,320 @@
+//! Import config vars from other sources into an app of a config file
+//!
+//! The sources are flat key/value documents: JSON objects, YAML maps, or files in the .env
+//! format.
+//!
+//! # Example
+//!
+//! ```rust
+//! let config_vars = import::read_file("secrets.json", import::Format::Json)?;
+//! let imported = import::merge_into(&mut app, &config.defaults, config_vars, false);
+//! ```
+use config::App;
+use serde_json::{self, Value};
+use serde_yaml;
+use std::collections::BTreeMap;
+use std::fs;
+use validation;
+
+/// Formats of the files to import
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Json,
+    Dotenv,
+    Yaml,
+}
+
+impl Format {
+    /// Names of the formats, as given on the command line
+    pub const NAMES: &'static [&'static str] = &["json", "dotenv", "yaml"];
+
+    /// Read a format from its name, the .env format by default
+    ///
+    /// # Arguments
+    /// * `name` - The name of the format
+    ///
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "json" => Format::Json,
+            "yaml" => Format::Yaml,
+            _ => Format::Dotenv,
+        }
+    }
+}
+
+/// Keys of the config vars imported into an app
+#[derive(Debug, PartialEq, Default)]
+pub struct Imported {
+    /// Config vars not set in the app before
+    pub added: Vec<String>,
+    /// Config vars replaced in the app
+    pub updated: Vec<String>,
+    /// Config vars with another value in the app, kept as they were
+    pub conflicts: Vec<String>,
+}
+
+/// Read the config vars of a file
+///
+/// # Arguments
+/// * `path` - A string containing the path to the file
+/// * `format` - The format of the file
+///
+/// # Result
+/// * `Result<BTreeMap<String, String>, String>` - The config vars or an Error message
+///
+pub fn read_file(path: &str, format: Format) -> Result<BTreeMap<String, String>, String> {
+    let config_vars = match format {
+        Format::Dotenv => from_dotenv(path)?,
+        Format::Json | Format::Yaml => {
+            let content = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            let document: Value = match format {
+                Format::Json => serde_json::from_str(&content).map_err(|err| err.to_string()),
+                _ => serde_yaml::from_str(&content).map_err(|err| err.to_string()),
+            }
+            .map_err(|err| format!("{}: {}", path, err))?;
+            flatten(document).map_err(|err| format!("{}: {}", path, err))?
+        }
+    };
+    for key in config_vars.keys() {
+        if !validation::is_config_var_key(key) {
+            return Err(format!("{}: invalid config var key \"{}\"", path, key));
+        }
+    }
+    Ok(config_vars)
+}
+
+/// Read the config vars of a file in the .env format
+///
+/// # Arguments
+/// * `path` - A string containing the path to the file
+///
+/// # Result
+/// * `Result<BTreeMap<String, String>, String>` - The config vars or an Error message
+///
+pub fn from_dotenv(path: &str) -> Result<BTreeMap<String, String>, String> {
+    let content = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    parse_dotenv(&content).map_err(|err| format!("{}:{}", path, err))
+}
+
+/// Parse the content of a file in the .env format
+///
+/// Every line is a `KEY=VALUE` pair, optionally prefixed with `export`. Blank lines and lines
+/// starting with `#` are skipped. A value in single quotes is kept as it is, a value in double
+/// quotes can contain the escapes `\n`, `\"` and `\\`, and an unquoted value ends before ` #`.
+///
+/// # Arguments
+/// * `content` - The content of the file
+///
+/// # Result
+/// * `Result<BTreeMap<String, String>, String>` - The config vars, or an Error message prefixed
+///   with the number of the line
+///
+fn parse_dotenv(content: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut config_vars: BTreeMap<String, String> = BTreeMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_start_matches("export ").trim_start();
+        let mut key_value = line.splitn(2, '=');
+        let key = key_value.next().unwrap_or("").trim();
+        let value = match key_value.next() {
+            Some(value) => value.trim(),
+            None => return Err(format!("{}: expected KEY=VALUE", index + 1)),
+        };
+        let value = unquote(value).map_err(|err| format!("{}: {}", index + 1, err))?;
+        config_vars.insert(key.to_string(), value);
+    }
+    Ok(config_vars)
+}
+
+/// Read a value of a file in the .env format, quoted or not
+fn unquote(value: &str) -> Result<String, String> {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some('\'') => {
+            let quoted = chars.as_str();
+            match quoted.find('\'') {
+                Some(end) => Ok(quoted[..end].to_string()),
+                None => Err("missing closing quote".to_string()),
+            }
+        }
+        Some('"') => {
+            let mut unquoted = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => return Ok(unquoted),
+                    '\\' => match chars.next() {
+                        Some('n') => unquoted.push('\n'),
+                        Some(escaped) => unquoted.push(escaped),
+                        None => break,
+                    },
+                    c => unquoted.push(c),
+                }
+            }
+            Err("missing closing quote".to_string())
+        }
+        _ => {
+            let end = value.find(" #").unwrap_or(value.len());
+            Ok(value[..end].trim_end().to_string())
+        }
+    }
+}
+
+/// Read the config vars of a flat document, numbers and booleans being converted to strings
+fn flatten(document: Value) -> Result<BTreeMap<String, String>, String> {
+    let pairs = match document {
+        Value::Object(pairs) => pairs,
+        _ => return Err("expected a map of config vars".to_string()),
+    };
+    let mut config_vars: BTreeMap<String, String> = BTreeMap::new();
+    for (key, value) in pairs {
+        let value = match value {
+            Value::String(value) => value,
+            Value::Number(number) => number.to_string(),
+            Value::Bool(flag) => flag.to_string(),
+            _ => {
+                return Err(format!(
+                    "expected a string, a number or a boolean for {}",
+                    key
+                ))
+            }
+        };
+        config_vars.insert(key, value);
+    }
+    Ok(config_vars)
+}
+
+/// Merge imported config vars into the settings of an app
+///
+/// A config var set with another value in the app, read from a source directive or removed by
+/// the app, is a conflict: it is only replaced when overwriting. A config var inherited with the
+/// same value from the defaults is not added.
+///
+/// # Arguments
+/// * `app` - The app of the config file
+/// * `defaults` - The defaults of the config file
+/// * `config_vars` - The config vars to import
+/// * `overwrite` - Replace the config vars in conflict
+///
+/// # Result
+/// * `Imported` - The keys of the config vars added, updated or in conflict
+///
+pub fn merge_into(
+    app: &mut App,
+    defaults: &BTreeMap<String, String>,
+    config_vars: BTreeMap<String, String>,
+    overwrite: bool,
+) -> Imported {
+    let mut imported = Imported::default();
+    for (key, value) in config_vars {
+        let removed = app.unset.contains(&key) || app.sources.contains_key(&key);
+        let current = if removed {
+            None
+        } else {
+            app.settings.get(&key).or_else(|| defaults.get(&key))
+        };
+        match current {
+            Some(current) if *current == value => continue,
+            None if !removed => imported.added.push(key.to_string()),
+            _ if overwrite => imported.updated.push(key.to_string()),
+            _ => {
+                imported.conflicts.push(key);
+                continue;
+            }
+        }
+        app.unset.retain(|k| *k != key);
+        app.sources.remove(&key);
+        app.settings.insert(key, value);
+    }
+    imported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_dotenv_files() {
+        let content = "# Secrets\nexport PORT=5000\n\nENV=test # comment\nURL=postgres://u:p@h/db?a=b\nQUOTED='a #b'\nESCAPED=\"line 1\\nline \\\"2\\\"\"\nEMPTY=\n";
+        let config_vars = parse_dotenv(content).unwrap();
+        assert_eq!(config_vars["PORT"], "5000");
+        assert_eq!(config_vars["ENV"], "test");
+        assert_eq!(config_vars["URL"], "postgres://u:p@h/db?a=b");
+        assert_eq!(config_vars["QUOTED"], "a #b");
+        assert_eq!(config_vars["ESCAPED"], "line 1\nline \"2\"");
+        assert_eq!(config_vars["EMPTY"], "");
+        assert_eq!(config_vars.len(), 6);
+
+        assert_eq!(
+            parse_dotenv("PORT=5000\nDEBUG\n"),
+            Err("2: expected KEY=VALUE".to_string())
+        );
+        assert_eq!(
+            parse_dotenv("ENV=\"test\n"),
+            Err("1: missing closing quote".to_string())
+        );
+    }
+
+    #[test]
+    fn should_read_flat_documents() {
+        let document =
+            serde_json::from_str("{\"PORT\": 5000, \"DEBUG\": false, \"ENV\": \"test\"}");
+        let config_vars = flatten(document.unwrap()).unwrap();
+        assert_eq!(config_vars["PORT"], "5000");
+        assert_eq!(config_vars["DEBUG"], "false");
+        assert_eq!(config_vars["ENV"], "test");
+
+        let document = serde_json::from_str("{\"AWS\": {\"KEY\": \"1\"}}");
+        assert_eq!(
+            flatten(document.unwrap()),
+            Err("expected a string, a number or a boolean for AWS".to_string())
+        );
+    }
+
+    #[test]
+    fn should_read_yaml_files() {
+        let config_vars = read_file("tests/import_test.yml", Format::Yaml).unwrap();
+        assert_eq!(config_vars.len(), 4);
+        assert_eq!(config_vars["PORT"], "5000");
+        assert_eq!(config_vars["DEBUG"], "false");
+
+        let config_vars = read_file("tests/config_test.yml", Format::Yaml);
+        assert_eq!(
+            config_vars,
+            Err(
+                "tests/config_test.yml: expected a string, a number or a boolean for apps"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn should_merge_into_apps() {
+        let mut app = App::new();
+        app.settings
+            .insert("ENV".to_string(), "staging".to_string());
+        app.unset.push("OLD_VAR".to_string());
+        let mut defaults: BTreeMap<String, String> = BTreeMap::new();
+        defaults.insert("LOG_LEVEL".to_string(), "info".to_string());
+
+        let mut config_vars: BTreeMap<String, String> = BTreeMap::new();
+        config_vars.insert("ENV".to_string(), "production".to_string());
+        config_vars.insert("LOG_LEVEL".to_string(), "info".to_string());
+        config_vars.insert("OLD_VAR".to_string(), "1".to_string());
+        config_vars.insert("PORT".to_string(), "5000".to_string());
+
+        let imported = merge_into(&mut app, &defaults, config_vars.clone(), false);
+        assert_eq!(imported.added, vec!["PORT"]);
+        assert!(imported.updated.is_empty());
+        assert_eq!(imported.conflicts, vec!["ENV", "OLD_VAR"]);
+        assert_eq!(app.settings["ENV"], "staging");
+        assert!(!app.settings.contains_key("LOG_LEVEL"));
+
+        let imported = merge_into(&mut app, &defaults, config_vars, true);
+        assert_eq!(imported.updated, vec!["ENV", "OLD_VAR"]);
+        assert_eq!(app.settings["OLD_VAR"], "1");
+        assert!(app.unset.is_empty());
+    }
+}
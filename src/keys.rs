@@ -0,0 +1,99 @@
+//! Select config vars by key, from a list of keys or regular expressions
+//!
+//! # Example
+//!
+//! ```rust
+//! use heroku_env::keys::KeyFilter;
+//!
+//! let filter = KeyFilter::new(&["PORT"], &["^AWS_"]).unwrap();
+//! assert!(filter.matches("PORT"));
+//! assert!(filter.matches("AWS_REGION"));
+//! assert!(!filter.matches("DATABASE_URL"));
+//! ```
+use regex::Regex;
+
+pub struct KeyFilter {
+    /// The keys selected
+    keys: Vec<String>,
+    /// Regular expressions matching the keys selected
+    patterns: Vec<Regex>,
+}
+
+impl KeyFilter {
+    /// Initialize a filter selecting the keys given, and the keys matching the patterns
+    ///
+    /// A filter without any key nor pattern selects every key.
+    ///
+    /// # Arguments
+    /// * `keys` - The keys to select
+    /// * `patterns` - Regular expressions, a key is selected if it contains a match
+    ///
+    /// # Result
+    /// * `Result<KeyFilter, String>` - The filter, or an Error message for an invalid pattern
+    ///
+    pub fn new(keys: &[&str], patterns: &[&str]) -> Result<Self, String> {
+        let mut regexes: Vec<Regex> = Vec::new();
+        for pattern in patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => regexes.push(regex),
+                Err(err) => return Err(format!("Invalid key pattern /{}/: {}", pattern, err)),
+            }
+        }
+        Ok(KeyFilter {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            patterns: regexes,
+        })
+    }
+
+    /// Initialize a filter selecting every key
+    pub fn all() -> Self {
+        KeyFilter {
+            keys: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Check if the filter selects every key
+    pub fn is_all(&self) -> bool {
+        self.keys.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Check if a key is selected by the filter
+    ///
+    /// # Arguments
+    /// * `key` - The key of a config var
+    ///
+    pub fn matches(&self, key: &str) -> bool {
+        self.is_all()
+            || self.keys.iter().any(|k| k == key)
+            || self.patterns.iter().any(|pattern| pattern.is_match(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_select_keys_and_patterns() {
+        let filter = KeyFilter::new(&["PORT"], &["^AWS_", "_URL$"]).unwrap();
+        assert!(!filter.is_all());
+        assert!(filter.matches("PORT"));
+        assert!(filter.matches("AWS_REGION"));
+        assert!(filter.matches("DATABASE_URL"));
+        assert!(!filter.matches("MY_AWS_REGION"));
+        assert!(!filter.matches("PORTS"));
+    }
+
+    #[test]
+    fn should_select_every_key_by_default() {
+        let filter = KeyFilter::all();
+        assert!(filter.is_all());
+        assert!(filter.matches("PORT"));
+    }
+
+    #[test]
+    fn should_fail_on_invalid_patterns() {
+        assert!(KeyFilter::new(&[], &["AWS_("]).is_err());
+    }
+}
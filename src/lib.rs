@@ -0,0 +1,75 @@
+//! heroku-env is a library to manage the config vars of Heroku apps, on which the `hke` command
+//! line utility is built
+//!
+//! Project Repository: (https://gitlab.com/jveillet/heroku-env-rs)[gitlab.com/jveillet/heroku-env-rs]
+//!
+//! It provides a client of the Heroku Platform API (`PlatformAPI`), and the configuration files
+//! of `hke` describing the config vars of several apps (`Config`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! extern crate heroku_env;
+//!
+//! use heroku_env::{Config, PlatformAPI};
+//!
+//! fn main() {
+//!     let mut config = Config::from_path("config.yml".to_string()).unwrap();
+//!     config.resolve_sources("config.yml").unwrap();
+//!     config.apply_defaults();
+//!
+//!     let mut client = PlatformAPI::new("my-heroku-api-token".to_string());
+//!     for app in &config.apps {
+//!         match client.patch_config_vars(app.name.to_string(), app.changes()) {
+//!             Ok(config_vars) => println!("{}: {} config vars", app.name, config_vars.len()),
+//!             Err(err) => eprintln!("{}: {}, {}", app.name, err.id, err.message),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! # Licence
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//!
+#[cfg(feature = "async")]
+extern crate futures;
+extern crate regex;
+extern crate reqwest;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
+extern crate serde_yaml;
+#[cfg(all(test, feature = "async"))]
+extern crate tokio;
+extern crate yaml_rust;
+
+mod api;
+pub mod config;
+pub mod heroku;
+#[cfg(feature = "async")]
+pub mod heroku_async;
+pub mod keys;
+mod merge;
+mod migration;
+pub mod schema;
+pub mod sources;
+pub mod validation;
+#[cfg(feature = "vault")]
+mod vault;
+mod yaml;
+
+pub use config::Config;
+pub use heroku::{PlatformAPI, PlatformError};
@@ -1,5 +1,8 @@
 //! heroku-env-rs is a command line utility to intercat with heroku config vars written in Rust
 //!
+//! The Heroku Platform API client and the config files are provided by the `heroku_env`
+//! library, this binary only implements the commands on top of it.
+//!
 //! Project Repository: (https://gitlab.com/jveillet/heroku-env-rs)[gitlab.com/jveillet/heroku-env-rs]
 //!
 //! # Licence
@@ -25,21 +28,39 @@
 //! CLI to interact with config vars on Heroku written in Rust.
 //!
 //! USAGE:
-//!    hke [SUBCOMMAND]
+//!    hke [OPTIONS] [SUBCOMMAND]
 //!
 //! FLAGS:
 //!    -h, --help       Prints help information
 //!    -V, --version    Prints version information
 //!
+//! OPTIONS:
+//!        --output-format <FORMAT>    Format of the output [default: human]  [possible values: human, json, yaml]
+//!
 //! SUBCOMMANDS:
-//!    help    Prints this message or the help of the given subcommand(s)
-//!    pull    Pull heroku config vars down to the local environment
-//!    push    Push local config vars to heroku
+//!    apply       Apply the changes of a plan file, if the apps did not change since the plan
+//!    check       Check the config vars of the apps against the schema of a config file
+//!    compare     Compare the config vars of apps side by side
+//!    drift       Report the config vars of the apps changed outside of a config file
+//!    export      Export the settings of the apps of a config file to other deployment formats
+//!    get         Print the values of config vars of an app
+//!    help        Prints this message or the help of the given subcommand(s)
+//!    history     Show the history of changes of the config vars of an app
+//!    import      Import config vars from a file into an app of a config file
+//!    migrate     Upgrade a config file to the latest version of the format
+//!    plan        Save the changes of a config file into a plan file, to apply them later
+//!    pull        Pull heroku config vars down to the local environment
+//!    push        Push local config vars to heroku
+//!    run         Run a local command with the config vars of an app
+//!    validate    Validate a config file
 //! ```
 //!
+extern crate atty;
+extern crate base64;
 extern crate clap;
 extern crate dotenv;
-extern crate reqwest;
+extern crate glob;
+extern crate heroku_env;
 
 #[macro_use]
 extern crate serde_derive;
@@ -48,25 +69,64 @@ extern crate serde;
 extern crate serde_yaml;
 
 extern crate serde_json;
+extern crate sha2;
 
 use clap::{App, Arg, SubCommand};
 
 use dotenv::dotenv;
 use std::env;
 
-mod heroku;
-use heroku as platform_api;
+mod apps;
+mod compare;
+mod confirm;
+mod drift;
+mod export;
+
+use heroku_env::heroku;
+use heroku_env::heroku as platform_api;
+
+mod history;
+mod import;
+use heroku_env::keys;
 
-mod config;
-use config as cfg;
+mod output;
+mod plan;
+mod run;
+use heroku_env::schema;
+use heroku_env::validation;
+mod watch;
 
-use std::collections::HashMap;
+use heroku_env::config;
+use heroku_env::config as cfg;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of seconds between two checks of the status of a pending release
+const RELEASE_POLL_INTERVAL: u64 = 2;
+
+/// Default number of seconds to wait for a pending release with `--wait`
+const DEFAULT_WAIT_TIMEOUT: u64 = 600;
 
 fn main() {
     let matches = App::new("heroku-env-rs")
         .version("0.1.8")
         .author("Jérémie Veillet <jeremie.veillet@gmail.com>")
         .about("CLI to interact with config vars on Heroku written in Rust.")
+        .arg(
+            Arg::with_name("output-format")
+                .long("output-format")
+                .value_name("FORMAT")
+                .help("Format of the output")
+                .possible_values(output::Format::NAMES)
+                .default_value("human")
+                .global(true)
+                .takes_value(true),
+        )
         .subcommand(
             SubCommand::with_name("push")
                 .about("Push local config vars to heroku")
@@ -75,24 +135,77 @@ fn main() {
                         .short("a")
                         .long("app")
                         .value_name("NAME")
-                        .help("App to run command against")
-                        .required_unless("config")
+                        .help("App to run command against, accepts glob patterns")
+                        .required_unless_one(&["config", "match"])
+                        .multiple(true)
+                        .number_of_values(1)
                         .takes_value(true),
                 )
+                .arg(team_arg())
+                .arg(match_arg())
                 .arg(
                     Arg::with_name("config")
                         .short("c")
                         .long("config")
                         .value_name("FILE")
                         .help("Sets a user defined config file in YAML format")
-                        .conflicts_with("app")
                         .takes_value(true),
                 )
+                .arg(
+                    Arg::with_name("env_file")
+                        .short("e")
+                        .long("env-file")
+                        .value_name("FILE")
+                        .help("Reads config vars from a file in the .env format")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("unset")
+                        .short("u")
+                        .long("unset")
+                        .value_name("KEY")
+                        .help("Removes a config var")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("wait")
+                        .short("w")
+                        .long("wait")
+                        .help("Wait for the releases to succeed or fail"),
+                )
+                .arg(
+                    Arg::with_name("wait_timeout")
+                        .long("wait-timeout")
+                        .value_name("SECONDS")
+                        .help("Maximum time to wait for a release with --wait [default: 600]")
+                        .requires("wait")
+                        .validator(is_number_of_seconds)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .short("y")
+                        .long("yes")
+                        .help("Push without asking for confirmation, except for protected apps"),
+                )
+                .arg(
+                    Arg::with_name("force_addon_vars")
+                        .long("force-addon-vars")
+                        .help("Allow updating config vars managed by add-ons (ex: DATABASE_URL)"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .requires("config")
+                        .help("Push the changes of the config file every time it is saved"),
+                )
                 .arg(
                     Arg::with_name("vars")
                         .value_name("KEY=VALUE")
                         .help("Key-Value pairs of config vars ")
-                        .required_unless("config")
+                        .required_unless_one(&["config", "env_file", "unset"])
                         .takes_value(true)
                         .multiple(true),
                 ),
@@ -100,13 +213,154 @@ fn main() {
         .subcommand(
             SubCommand::with_name("pull")
                 .about("Pull heroku config vars down to the local environment")
+                .arg(
+                    Arg::with_name("app")
+                        .short("a")
+                        .long("app")
+                        .value_name("NAME")
+                        .help("App to run command against, accepts glob patterns")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required_unless("match")
+                        .takes_value(true),
+                )
+                .arg(team_arg())
+                .arg(match_arg())
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Save the output to a config file in YAML format")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .requires("output")
+                        .help("Only update the pulled apps of the output file, keeping the others"),
+                )
+                .arg(force_arg())
+                .arg(
+                    Arg::with_name("keys")
+                        .short("k")
+                        .long("keys")
+                        .value_name("KEY")
+                        .help("Only pull these config vars")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("keys_match")
+                        .long("keys-match")
+                        .value_name("REGEX")
+                        .help("Only pull the config vars whose key matches a regular expression")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .about("Print the values of config vars of an app")
+                .arg(
+                    Arg::with_name("app")
+                        .short("a")
+                        .long("app")
+                        .value_name("NAME")
+                        .help("App to run command against")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("keys")
+                        .value_name("KEY")
+                        .help("Keys of the config vars, their values are printed one per line")
+                        .required(true)
+                        .multiple(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Run a local command with the config vars of an app")
                 .arg(
                     Arg::with_name("app")
                         .short("a")
                         .long("app")
                         .value_name("NAME")
                         .help("App to run command against")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("only")
+                        .long("only")
+                        .value_name("KEY")
+                        .help("Only add these config vars to the environment")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("exclude")
+                        .long("exclude")
+                        .value_name("KEY")
+                        .help("Do not add these config vars to the environment")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("env_file")
+                        .short("e")
+                        .long("env-file")
+                        .value_name("FILE")
+                        .help("Reads local config vars overriding the ones of the app from a file in the .env format")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("command")
+                        .value_name("COMMAND")
+                        .help("Command to run, after --")
+                        .required(true)
+                        .multiple(true)
+                        .last(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compare the config vars of apps side by side")
+                .arg(
+                    Arg::with_name("apps")
+                        .value_name("APP")
+                        .help("Apps to compare")
+                        .required(true)
                         .multiple(true)
+                        .min_values(2)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("only_diff")
+                        .long("only-diff")
+                        .help("Only show the config vars missing or different in one of the apps"),
+                )
+                .arg(
+                    Arg::with_name("show_values")
+                        .long("show-values")
+                        .help("Show the values of the config vars instead of masking them"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("plan")
+                .about("Save the changes of a config file into a plan file, to apply them later")
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format with the changes")
                         .required(true)
                         .takes_value(true),
                 )
@@ -115,141 +369,1411 @@ fn main() {
                         .short("o")
                         .long("output")
                         .value_name("FILE")
-                        .help("Save the output to a config file in YAML format")
+                        .help("Save the plan to a file in JSON format")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(force_arg())
+                .arg(
+                    Arg::with_name("force_addon_vars")
+                        .long("force-addon-vars")
+                        .help("Allow updating config vars managed by add-ons (ex: DATABASE_URL)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("apply")
+                .about("Apply the changes of a plan file, if the apps did not change since the plan")
+                .arg(
+                    Arg::with_name("plan")
+                        .value_name("PLAN")
+                        .help("Plan file in JSON format")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("wait")
+                        .short("w")
+                        .long("wait")
+                        .help("Wait for the releases to succeed or fail"),
+                )
+                .arg(
+                    Arg::with_name("wait_timeout")
+                        .long("wait-timeout")
+                        .value_name("SECONDS")
+                        .help("Maximum time to wait for a release with --wait [default: 600]")
+                        .requires("wait")
+                        .validator(is_number_of_seconds)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("drift")
+                .about("Report the config vars of the apps changed outside of a config file")
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format listing the apps")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("junit")
+                        .long("junit")
+                        .value_name("FILE")
+                        .help("Save a report in JUnit XML format, for CI servers")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Import config vars from a file into an app of a config file")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("FORMAT")
+                        .help("Format of the imported file")
+                        .possible_values(import::Format::NAMES)
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("File with a flat key/value document")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("app")
+                        .short("a")
+                        .long("app")
+                        .value_name("NAME")
+                        .help("App to import the config vars into, added if missing")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format to update, created if missing")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("overwrite")
+                        .long("overwrite")
+                        .help("Replace the config vars with another value in the config file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Export the settings of the apps of a config file to other deployment formats")
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format to export")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Format of the exported files")
+                        .possible_values(export::Format::NAMES)
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("dir")
+                        .short("d")
+                        .long("dir")
+                        .value_name("DIR")
+                        .help("Directory to save a file per app into, created if missing")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(force_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("Show the history of changes of the config vars of an app")
+                .arg(
+                    Arg::with_name("app")
+                        .short("a")
+                        .long("app")
+                        .value_name("NAME")
+                        .help("App to run command against")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("key")
+                        .value_name("KEY")
+                        .help("Only show the changes of this config var")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Validate a config file")
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format to validate")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Check the config vars of the apps against the schema of a config file")
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format declaring the schema")
+                        .required(true)
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("local")
+                        .short("l")
+                        .long("local")
+                        .help("Only check the settings of the config file, not the apps on Heroku"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Upgrade a config file to the latest version of the format")
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Config file in YAML format to upgrade")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Write the upgraded config file to another file")
+                        .takes_value(true),
+                )
+                .arg(force_arg()),
+        )
         .get_matches();
 
     dotenv().expect("Couldn't find a .env file. Please create a .env file first.");
 
+    let format = output::Format::from_name(matches.value_of("output-format").unwrap_or("human"));
+    let mut output = output::Output::new(format, matches.subcommand_name().unwrap_or(""));
+
     match matches.subcommand() {
         ("push", Some(push_matches)) => {
-            if push_matches.is_present("app") {
-                if let Some(a) = push_matches.value_of("app") {
-                    let app_name = a.to_string();
-                    let settings = config_vars_from_args(push_matches);
-                    push_single_app(&app_name, settings);
+            let options = PushOptions::from_matches(push_matches);
+            match config_from_sources(push_matches) {
+                Ok(_) if options.watch && format != output::Format::Human => output.error(
+                    "--watch only supports the human output format, it never ends".to_string(),
+                ),
+                Ok(heroku_config) if options.watch => {
+                    watch(push_matches, heroku_config, &options, &mut output)
                 }
-            }
-            if let Some(_c) = push_matches.value_of("config") {
-                if let Some(config_matches) = push_matches.value_of("config") {
-                    push(config_matches.to_string());
+                Ok(heroku_config) => {
+                    update_config_vars(heroku_config, &options, &mut output);
                 }
+                Err(err) => output.error(err),
             }
         }
         ("pull", Some(pull_matches)) => {
-            if pull_matches.is_present("app") {
-                if let Some(apps) = pull_matches.values_of("app") {
+            match (resolve_apps(pull_matches), key_filter(pull_matches)) {
+                (Ok(apps), Ok(keys)) => {
                     let mut path = String::new();
-                    if pull_matches.is_present("output") {
-                        if let Some(output) = pull_matches.value_of("output") {
-                            path = output.to_string();
-                        }
+                    if let Some(output_path) = pull_matches.value_of("output") {
+                        path = output_path.to_string();
                     }
-                    pull(apps, &path)
+                    let options = PullOptions {
+                        merge: pull_matches.is_present("merge"),
+                        force: pull_matches.is_present("force"),
+                        keys,
+                    };
+                    pull(&apps, &path, &options, &mut output)
                 }
+                (Err(err), _) | (_, Err(err)) => output.error(err),
             }
         }
-        ("", None) => {
-            println!("No subcommand was used. For a list of subcommands, please try hke --help")
-        } // If no subcommand was used it'll match the tuple ("", None)
-        _ => unreachable!(), // If all subcommands are defined above, anything else is unreachable!()
-    }
-}
-
-/// Push heroku config vars to the pipelines
-///
-/// # Arguments
-///
-/// * `config_file_path` - The config file's path in the file system.
-///
-fn push(config_file_path: String) {
-    match cfg::Config::from_path(config_file_path) {
-        Ok(heroku_config) => update_config_vars(heroku_config),
-        Err(err) => println!("Error: {}", err),
-    }
-}
-
-/// Push heroku config vars for a single app
+        ("get", Some(get_matches)) => {
+            if let (Some(app), Some(keys)) =
+                (get_matches.value_of("app"), get_matches.values_of("keys"))
+            {
+                output.print_errors_on_stderr();
+                get(app, &keys.collect::<Vec<&str>>(), &mut output);
+            }
+        }
+        ("run", Some(run_matches)) => {
+            if let (Some(app), Some(command)) = (
+                run_matches.value_of("app"),
+                run_matches.values_of("command"),
+            ) {
+                output.print_errors_on_stderr();
+                let options = RunOptions {
+                    only: values_of(run_matches, "only"),
+                    exclude: values_of(run_matches, "exclude"),
+                    env_file: run_matches.value_of("env_file"),
+                };
+                run(app, &command.collect::<Vec<&str>>(), &options, &mut output);
+            }
+        }
+        ("compare", Some(compare_matches)) => {
+            compare(
+                &values_of(compare_matches, "apps"),
+                compare_matches.is_present("only_diff"),
+                compare_matches.is_present("show_values"),
+                &mut output,
+            );
+        }
+        ("plan", Some(plan_matches)) => {
+            if let (Some(config_file_path), Some(plan_path)) = (
+                plan_matches.value_of("config"),
+                plan_matches.value_of("output"),
+            ) {
+                let options = PlanOptions {
+                    force: plan_matches.is_present("force"),
+                    force_addon_vars: plan_matches.is_present("force_addon_vars"),
+                };
+                make_plan(config_file_path, plan_path, &options, &mut output);
+            }
+        }
+        ("apply", Some(apply_matches)) => {
+            if let Some(plan_path) = apply_matches.value_of("plan") {
+                apply(plan_path, wait_timeout(apply_matches), &mut output);
+            }
+        }
+        ("drift", Some(drift_matches)) => {
+            if let Some(config_file_path) = drift_matches.value_of("config") {
+                drift(
+                    config_file_path,
+                    drift_matches.value_of("junit"),
+                    &mut output,
+                );
+            }
+        }
+        ("import", Some(import_matches)) => {
+            if let (Some(format), Some(file), Some(app), Some(config_file_path)) = (
+                import_matches.value_of("from"),
+                import_matches.value_of("file"),
+                import_matches.value_of("app"),
+                import_matches.value_of("config"),
+            ) {
+                import(
+                    (import::Format::from_name(format), file),
+                    app,
+                    config_file_path,
+                    import_matches.is_present("overwrite"),
+                    &mut output,
+                );
+            }
+        }
+        ("export", Some(export_matches)) => {
+            if let (Some(config_file_path), Some(format), Some(dir)) = (
+                export_matches.value_of("config"),
+                export_matches.value_of("format"),
+                export_matches.value_of("dir"),
+            ) {
+                export(
+                    config_file_path,
+                    export::Format::from_name(format),
+                    dir,
+                    export_matches.is_present("force"),
+                    &mut output,
+                );
+            }
+        }
+        ("history", Some(history_matches)) => {
+            if let Some(app) = history_matches.value_of("app") {
+                history(app, history_matches.value_of("key"), &mut output);
+            }
+        }
+        ("validate", Some(validate_matches)) => {
+            if let Some(config_file_path) = validate_matches.value_of("config") {
+                validate(config_file_path, &mut output);
+            }
+        }
+        ("check", Some(check_matches)) => {
+            if let Some(config_file_path) = check_matches.value_of("config") {
+                check(
+                    config_file_path,
+                    check_matches.is_present("local"),
+                    &mut output,
+                );
+            }
+        }
+        ("migrate", Some(migrate_matches)) => {
+            if let Some(config_file_path) = migrate_matches.value_of("config") {
+                let output_path = migrate_matches.value_of("output").unwrap_or("");
+                migrate(
+                    config_file_path,
+                    output_path,
+                    migrate_matches.is_present("force"),
+                    &mut output,
+                );
+            }
+        }
+        ("", None) => {
+            println!("No subcommand was used. For a list of subcommands, please try hke --help");
+            return;
+        } // If no subcommand was used it'll match the tuple ("", None)
+        _ => unreachable!(), // If all subcommands are defined above, anything else is unreachable!()
+    }
+
+    if !output.finish() {
+        process::exit(1);
+    }
+}
+
+/// Options of the push command
+struct PushOptions {
+    /// Allow the update of config vars managed by add-ons
+    force_addon_vars: bool,
+    /// Maximum time to wait for the releases created by the update to succeed or fail, `None`
+    /// to not wait for them
+    wait: Option<Duration>,
+    /// Push without asking for confirmation, except for protected apps
+    yes: bool,
+    /// Push the changes of the config file every time it is saved
+    watch: bool,
+}
+
+impl PushOptions {
+    /// Read the push options from the command line arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `push_matches` - List of command line arguments matchers (see clap documentation)
+    ///
+    fn from_matches(push_matches: &clap::ArgMatches) -> Self {
+        PushOptions {
+            force_addon_vars: push_matches.is_present("force_addon_vars"),
+            wait: wait_timeout(push_matches),
+            yes: push_matches.is_present("yes"),
+            watch: push_matches.is_present("watch"),
+        }
+    }
+}
+
+/// Options of the pull command
+struct PullOptions {
+    /// Merge the apps into the output file instead of overwriting it
+    merge: bool,
+    /// Overwrite the output file if it already exists
+    force: bool,
+    /// The config vars to pull
+    keys: keys::KeyFilter,
+}
+
+/// Collect every change to push from the sources given on the command line
+///
+/// The changes of the config file, the env file, the `--unset` options and the KEY=VALUE
+/// arguments are merged app by app, in this order, so that each app is updated only once.
 ///
 /// # Arguments
 ///
-/// * `app_name` - The app to update.
-/// * `settings` - a HashMap containing list of config vars (key-value pairs).
+/// * `push_matches` - List of command line arguments matchers (see clap documentation)
 ///
-fn push_single_app(app_name: &str, settings: HashMap<String, String>) {
-    match cfg::Config::from_app(&app_name, settings) {
-        Ok(heroku_config) => update_config_vars(heroku_config),
-        Err(err) => println!("Error: {}", err),
+/// # Result
+///
+/// * `Result<Config, String>` - The changes of every app or an Error message
+///
+fn config_from_sources(push_matches: &clap::ArgMatches) -> Result<cfg::Config, String> {
+    // The config file is the base, its schema is kept to push its default values once the apps
+    // on heroku are known
+    let mut config = match push_matches.value_of("config") {
+        Some(config_file_path) => load_resolved_config(config_file_path)?,
+        None => cfg::Config::new(),
+    };
+    config.apply_defaults();
+
+    let has_changes = push_matches.is_present("vars")
+        || push_matches.is_present("env_file")
+        || push_matches.is_present("unset");
+    let has_apps = push_matches.is_present("app") || push_matches.is_present("match");
+    if !has_apps {
+        if has_changes {
+            return Err(
+                "Config vars can only be pushed to apps selected with --app or --match."
+                    .to_string(),
+            );
+        }
+        return Ok(config);
+    }
+
+    let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    if let Some(env_file_path) = push_matches.value_of("env_file") {
+        settings.extend(config_vars_from_env_file(env_file_path)?);
     }
+    settings.extend(config_vars_from_args(push_matches)?);
+
+    let mut unset: Vec<String> = Vec::new();
+    if let Some(keys) = push_matches.values_of("unset") {
+        unset.extend(keys.map(|key| key.to_string()));
+    }
+
+    let mut apps_config = cfg::Config::from_apps(&resolve_apps(push_matches)?, BTreeMap::new())?;
+    for app in &mut apps_config.apps {
+        app.unset = unset.clone();
+        app.settings = settings.clone();
+    }
+    config.merge(apps_config);
+    Ok(config)
 }
 
 /// Pull config vars down to the local machine for one or more apps
 ///
 /// # Arguments
 ///
-/// * `apps` - An iterator to get arguments from command line
+/// * `apps` - The names of the apps to pull
 /// * `path` - A String containing the path to the file to write config vars into. Leave blank
 /// to not create a file
+/// * `options` - Options of the pull command
+/// * `output` - The output of the command
 ///
-fn pull(apps: clap::Values, path: &str) {
+fn pull(apps: &[String], path: &str, options: &PullOptions, output: &mut output::Output) {
     let mut client = heroku_client();
 
     let mut config: cfg::Config = cfg::Config::new();
     for app in apps {
         match client.get_config_vars(app.to_string()) {
             Ok(config_vars) => {
+                let attachments = match client.get_addon_attachments(app.to_string()) {
+                    Ok(attachments) => attachments,
+                    Err(platform_error) => {
+                        output.platform_error(&platform_error);
+                        Vec::new()
+                    }
+                };
+                let config_vars: BTreeMap<String, String> = config_vars_to_map(config_vars)
+                    .into_iter()
+                    .filter(|(key, _)| options.keys.matches(key))
+                    .collect();
+                let mut addons: BTreeMap<String, String> = BTreeMap::new();
+                for key in config_vars.keys() {
+                    if let Some(attachment) = attachments.iter().find(|a| a.manages(key)) {
+                        addons.insert(key.to_string(), attachment.addon.name.to_string());
+                    }
+                }
                 let mut heroku_app = cfg::App::new();
-                println!("{}", app.to_string());
                 heroku_app.name = app.to_string();
-                for arg in config_vars {
-                    println!("{}", arg);
-                    let tuple: Vec<&str> = arg.split('=').collect();
-                    heroku_app
-                        .settings
-                        .insert(tuple[0].to_string(), tuple[1].to_string());
-                }
-                println!("-------------------------");
+                heroku_app.settings = config_vars.clone();
+                output.result(&output::PulledApp {
+                    name: app.to_string(),
+                    config_vars,
+                    addons,
+                });
                 config.apps.push(heroku_app);
             }
+            Err(platform_error) => output.platform_error(&platform_error),
+        }
+    }
+    if !path.to_string().is_empty() {
+        let result = if options.merge {
+            config.save_merged(path, &options.keys)
+        } else {
+            config
+                .save(path, options.force)
+                .map_err(|err| err.to_string())
+        };
+        match result {
+            Ok(s) => output.message(s),
+            Err(err) => output.error(err),
+        }
+    }
+}
+
+/// Options of the plan command
+struct PlanOptions {
+    /// Overwrite the plan file if it already exists
+    force: bool,
+    /// Allow the update of config vars managed by add-ons
+    force_addon_vars: bool,
+}
+
+/// Save the changes of the apps of a config file into a plan file
+///
+/// The plan is not saved if the config vars of one of the apps could not be planned.
+///
+/// # Arguments
+///
+/// * `config_file_path` - The path to the config file
+/// * `plan_path` - The path to the plan file
+/// * `options` - Options of the plan command
+/// * `output` - The output of the command
+///
+fn make_plan(
+    config_file_path: &str,
+    plan_path: &str,
+    options: &PlanOptions,
+    output: &mut output::Output,
+) {
+    let mut config = match load_resolved_config(config_file_path) {
+        Ok(config) => config,
+        Err(err) => return output.error(err),
+    };
+    config.apply_defaults();
+
+    let mut client = heroku_client();
+    let mut plan = plan::Plan::new();
+    let mut complete = true;
+    for mut app in config.apps {
+        let remote = match client.get_config_vars(app.name.to_string()) {
+            Ok(config_vars) => config_vars_to_map(config_vars),
+            Err(platform_error) => return output.platform_error(&platform_error),
+        };
+        app.apply_schema_defaults(&config.schema, &remote);
+        if app.is_empty() {
+            continue;
+        }
+        if !options.force_addon_vars {
+            match addon_managed_vars(&mut client, &app) {
+                Ok(ref keys) if keys.is_empty() => {}
+                Ok(keys) => {
+                    output.error(addon_vars_refusal(&app.name, &keys));
+                    complete = false;
+                    continue;
+                }
+                Err(platform_error) => return output.platform_error(&platform_error),
+            }
+        }
+        let app_plan = plan::AppPlan::new(&remote, &app);
+        output.result(&output::PlannedApp {
+            name: app.name.to_string(),
+            protected: app.protected,
+            changes: plan::changes(&remote, &app),
+        });
+        if !app_plan.changes.is_empty() {
+            plan.apps.push(app_plan);
+        }
+    }
+    if !complete {
+        return;
+    }
+    match plan.save(plan_path, options.force) {
+        Ok(s) => output.message(s),
+        Err(err) => output.error(err.to_string()),
+    }
+}
+
+/// Apply the changes of a plan file
+///
+/// Nothing is applied if the config vars of one of the apps changed since the plan was made.
+/// The changes of each app are applied with a single update (`patch_config_vars`), setting and
+/// removing its config vars in one release. Like `push`, the name of a protected app must be
+/// typed in a terminal to apply its changes.
+///
+/// # Arguments
+///
+/// * `plan_path` - The path to the plan file
+/// * `wait` - Maximum time to wait for the releases to succeed or fail, `None` to not wait
+/// * `output` - The output of the command
+///
+fn apply(plan_path: &str, wait: Option<Duration>, output: &mut output::Output) {
+    let plan = match plan::Plan::from_path(plan_path) {
+        Ok(plan) => plan,
+        Err(err) => return output.error(err),
+    };
+
+    let mut client = heroku_client();
+    let mut outdated = false;
+    let mut remotes: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    for app_plan in &plan.apps {
+        match client.get_config_vars(app_plan.name.to_string()) {
+            Ok(config_vars) => {
+                let remote = config_vars_to_map(config_vars);
+                if plan::remote_hash(&remote) != app_plan.remote_hash {
+                    output.error(format!(
+                        "the config vars of app {} changed since the plan was made, \
+                         run hke plan again",
+                        app_plan.name
+                    ));
+                    outdated = true;
+                }
+                remotes.insert(app_plan.name.to_string(), remote);
+            }
+            Err(platform_error) => return output.platform_error(&platform_error),
+        }
+    }
+    if outdated {
+        return;
+    }
+    if plan.apps.is_empty() {
+        return output.message("No changes to apply.".to_string());
+    }
+
+    let interactive = confirm::is_interactive();
+    let confirmed: Vec<plan::AppPlan> = plan
+        .apps
+        .into_iter()
+        .filter(|app_plan| {
+            !app_plan.protected
+                || confirm_app_plan(app_plan, &remotes[&app_plan.name], interactive, output)
+        })
+        .collect();
+    for app_plan in confirmed {
+        if !patch_app(&mut client, &app_plan.name, app_plan.changes, wait, output) {
+            break;
+        }
+    }
+}
+
+/// Show the changes of a protected app of a plan and ask the user to type its name
+///
+/// # Arguments
+///
+/// * `app_plan` - The changes of the protected app
+/// * `remote` - The config vars of the app on heroku
+/// * `interactive` - The standard input is a terminal, the changes are refused otherwise
+/// * `output` - The output of the command, to show the changes
+///
+/// # Result
+///
+/// * `bool` - true if the changes were confirmed
+///
+fn confirm_app_plan(
+    app_plan: &plan::AppPlan,
+    remote: &BTreeMap<String, String>,
+    interactive: bool,
+    output: &mut output::Output,
+) -> bool {
+    if !interactive {
+        output.error(format!(
+            "app {} is protected, its name must be typed to apply the plan in a terminal",
+            app_plan.name
+        ));
+        return false;
+    }
+    let planned_app = output::PlannedApp {
+        name: app_plan.name.to_string(),
+        protected: true,
+        changes: plan::diff(remote, &app_plan.changes),
+    };
+    output.progress(planned_app.to_string().trim_end());
+    let question = format!(
+        "{} is protected, type its name to apply the changes: ",
+        app_plan.name
+    );
+    let accepted = ask(&question, output) == app_plan.name;
+    if !accepted {
+        output.message(format!(
+            "Skipping update for app {}, the changes were not confirmed.",
+            app_plan.name
+        ));
+    }
+    accepted
+}
+
+/// Options of the run command
+struct RunOptions<'a> {
+    /// The keys of the config vars to add, every config var is added if empty
+    only: Vec<&'a str>,
+    /// The keys of the config vars not to add
+    exclude: Vec<&'a str>,
+    /// A file in the .env format with local config vars overriding the ones of the app
+    env_file: Option<&'a str>,
+}
+
+/// Run a local command with the config vars of an app added to the environment
+///
+/// On success, hke exits with the exit code of the command.
+///
+/// # Arguments
+///
+/// * `app_name` - The name of the app
+/// * `command` - The program to run followed by its arguments
+/// * `options` - Options of the run command
+/// * `output` - The output of the command, failed if the command could not be started
+///
+fn run(app_name: &str, command: &[&str], options: &RunOptions, output: &mut output::Output) {
+    let overrides = match options.env_file {
+        Some(path) => match config_vars_from_env_file(path) {
+            Ok(overrides) => overrides,
+            Err(err) => return output.error(err),
+        },
+        None => BTreeMap::new(),
+    };
+    let only = if options.only.is_empty() {
+        keys::KeyFilter::all()
+    } else {
+        match keys::KeyFilter::new(&options.only, &[]) {
+            Ok(only) => only,
+            Err(err) => return output.error(err),
+        }
+    };
+
+    let mut client = heroku_client();
+    match client.get_config_vars(app_name.to_string()) {
+        Ok(config_vars) => {
+            let vars = run::environment(
+                config_vars_to_map(config_vars),
+                &only,
+                &options.exclude,
+                overrides,
+            );
+            match run::exec(command[0], &command[1..], &vars) {
+                Ok(code) => process::exit(code),
+                Err(err) => output.error(err),
+            }
+        }
+        Err(platform_error) => output.platform_error(&platform_error),
+    }
+}
+
+/// Report the values of config vars of an app
+///
+/// Nothing is printed if one of the config vars is not set, so that scripts never use a
+/// partial output.
+///
+/// # Arguments
+///
+/// * `app_name` - The name of the app
+/// * `keys` - The keys of the config vars
+/// * `output` - The output of the command, failed if a config var is not set
+///
+fn get(app_name: &str, keys: &[&str], output: &mut output::Output) {
+    let mut client = heroku_client();
+
+    match client.get_config_vars(app_name.to_string()) {
+        Ok(config_vars) => {
+            let config_vars = config_vars_to_map(config_vars);
+            let missing: Vec<&str> = keys
+                .iter()
+                .filter(|key| !config_vars.contains_key(**key))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                return output.error(format!(
+                    "config var{} {} {} not set on app {}",
+                    if missing.len() == 1 { "" } else { "s" },
+                    missing.join(", "),
+                    if missing.len() == 1 { "is" } else { "are" },
+                    app_name
+                ));
+            }
+            output.result(&output::ConfigVarValues {
+                name: app_name.to_string(),
+                config_vars: config_vars
+                    .into_iter()
+                    .filter(|(key, _)| keys.contains(&key.as_str()))
+                    .collect(),
+                keys: keys.iter().map(|key| key.to_string()).collect(),
+            });
+        }
+        Err(platform_error) => output.platform_error(&platform_error),
+    }
+}
+
+/// Export the settings of every app of a config file, in a file per app
+///
+/// # Arguments
+///
+/// * `config_file_path` - The path to the config file
+/// * `format` - The format of the exported files
+/// * `dir` - The directory of the exported files, created if missing
+/// * `force` - Overwrite the exported files if they already exist
+/// * `output` - The output of the command
+///
+fn export(
+    config_file_path: &str,
+    format: export::Format,
+    dir: &str,
+    force: bool,
+    output: &mut output::Output,
+) {
+    let mut config = match load_resolved_config(config_file_path) {
+        Ok(config) => config,
+        Err(err) => return output.error(err),
+    };
+    config.apply_defaults();
+    if let Err(err) = fs::create_dir_all(dir) {
+        return output.error(format!("{}: {}", dir, err));
+    }
+
+    // The exported files describe new deployments, without config vars yet
+    let no_config_vars = BTreeMap::new();
+    let schema = &config.schema;
+    for app in &mut config.apps {
+        app.apply_schema_defaults(schema, &no_config_vars);
+    }
+    for app in &config.apps {
+        if app.settings.is_empty() {
+            output.message(format!(
+                "Skipping export for app {}, no settings were found.",
+                app.name
+            ));
+            continue;
+        }
+        let path = Path::new(dir).join(export::file_name(format, &app.name));
+        let path = path.to_string_lossy();
+        let saved = export::render(format, &app.name, &app.settings).and_then(|content| {
+            cfg::Config::check_overwrite(&path, force)
+                .and_then(|_| cfg::Config::write(&path, &content))
+                .map_err(|err| err.to_string())
+        });
+        match saved {
+            Ok(_) => output.message(format!("Successfully exported {} to {}", app.name, path)),
+            Err(err) => output.error(format!("{}: {}", app.name, err)),
+        }
+    }
+}
+
+/// Import the config vars of a file into an app of a config file
+///
+/// # Arguments
+///
+/// * `source` - The format and the path of the imported file
+/// * `app_name` - The name of the app, added to the config file if missing
+/// * `config_file_path` - The path to the config file, created if missing
+/// * `overwrite` - Replace the config vars with another value in the config file
+/// * `output` - The output of the command
+///
+fn import(
+    source: (import::Format, &str),
+    app_name: &str,
+    config_file_path: &str,
+    overwrite: bool,
+    output: &mut output::Output,
+) {
+    let (format, file) = source;
+    let config_vars = match import::read_file(file, format) {
+        Ok(config_vars) => config_vars,
+        Err(err) => return output.error(err),
+    };
+    let mut config = if Path::new(config_file_path).exists() {
+        match load_config(config_file_path) {
+            Ok(config) => config,
+            Err(err) => return output.error(err),
+        }
+    } else {
+        cfg::Config::new()
+    };
+
+    if !config.apps.iter().any(|app| app.name == app_name) {
+        let mut app = cfg::App::new();
+        app.name = app_name.to_string();
+        config.apps.push(app);
+    }
+    let defaults = config.defaults.clone();
+    let imported = match config.apps.iter_mut().find(|app| app.name == app_name) {
+        Some(app) => import::merge_into(app, &defaults, config_vars, overwrite),
+        None => return,
+    };
+    let keys: Vec<String> = imported
+        .added
+        .iter()
+        .chain(imported.updated.iter())
+        .cloned()
+        .collect();
+    output.result(&output::ImportedApp {
+        name: app_name.to_string(),
+        file: file.to_string(),
+        added: imported.added,
+        updated: imported.updated,
+        conflicts: imported.conflicts,
+    });
+
+    match config.save_imported(config_file_path, app_name, &keys) {
+        Ok(s) => output.message(s),
+        Err(err) => output.error(err),
+    }
+}
+
+/// Report the config vars of the apps of a config file changed on heroku
+///
+/// # Arguments
+///
+/// * `config_file_path` - The path to the config file
+/// * `junit_path` - The path to a report in JUnit XML format, if any
+/// * `output` - The output of the command, failed if one of the apps has drifted
+///
+fn drift(config_file_path: &str, junit_path: Option<&str>, output: &mut output::Output) {
+    let mut config = match load_resolved_config(config_file_path) {
+        Ok(config) => config,
+        Err(err) => return output.error(err),
+    };
+    config.apply_defaults();
+
+    let mut client = heroku_client();
+    let mut apps: Vec<output::AppDrift> = Vec::new();
+    let mut errors: Vec<(String, String)> = Vec::new();
+    for app in &config.apps {
+        match client.get_config_vars(app.name.to_string()) {
+            Ok(config_vars) => {
+                let app_drift = output::AppDrift {
+                    name: app.name.to_string(),
+                    keys: drift::classify(app, &config_vars_to_map(config_vars)),
+                };
+                output.result(&app_drift);
+                apps.push(app_drift);
+            }
             Err(platform_error) => {
-                println!(
-                    "PlatformError: {}, {}",
-                    platform_error.id, platform_error.message
-                );
+                output.platform_error(&platform_error);
+                errors.push((app.name.to_string(), platform_error.message));
             }
         }
     }
-    if !path.to_string().is_empty() {
-        match config.save(path) {
-            Ok(s) => println!("{}", s),
-            Err(err) => println!("Error: {}", err),
+
+    let drifted = apps.iter().filter(|app| app.has_drifted()).count();
+    output.message(format!(
+        "{} of {} apps drifted from {}",
+        drifted,
+        apps.len(),
+        config_file_path
+    ));
+    if drifted > 0 {
+        output.fail();
+    }
+    if let Some(path) = junit_path {
+        match fs::write(path, output::junit_report(&apps, &errors)) {
+            Ok(_) => output.message(format!("Successfully saved JUnit report at {}", path)),
+            Err(err) => output.error(format!("{}: {}", path, err)),
         }
     }
 }
 
+/// Report the config vars of several apps side by side
+///
+/// # Arguments
+///
+/// * `app_names` - The names of the apps
+/// * `only_diff` - Only report the config vars missing or different in one of the apps
+/// * `show_values` - Report the values of the config vars instead of masking them
+/// * `output` - The output of the command
+///
+fn compare(app_names: &[&str], only_diff: bool, show_values: bool, output: &mut output::Output) {
+    let mut client = heroku_client();
+
+    let mut apps: Vec<BTreeMap<String, String>> = Vec::new();
+    for app_name in app_names {
+        match client.get_config_vars(app_name.to_string()) {
+            Ok(config_vars) => apps.push(config_vars_to_map(config_vars)),
+            Err(platform_error) => return output.platform_error(&platform_error),
+        }
+    }
+
+    let config_vars = compare::compare_config_vars(&apps)
+        .into_iter()
+        .filter(|row| !only_diff || row.differs())
+        .map(|row| output::ComparedConfigVar {
+            differs: row.differs(),
+            values: if show_values {
+                row.values.clone()
+            } else {
+                row.masked_values()
+            },
+            key: row.key,
+        })
+        .collect();
+    output.result(&output::Comparison {
+        apps: app_names.iter().map(|app| app.to_string()).collect(),
+        config_vars,
+    });
+}
+
+/// Report the timeline of the changes of config vars of an app, from the latest release
+///
+/// # Arguments
+///
+/// * `app_name` - The name of the app
+/// * `key` - Only report the releases changing this config var, if any
+/// * `output` - The output of the command
+///
+fn history(app_name: &str, key: Option<&str>, output: &mut output::Output) {
+    let mut client = heroku_client();
+
+    match client.get_releases(app_name.to_string()) {
+        Ok(releases) => {
+            let mut app_history = output::AppHistory {
+                name: app_name.to_string(),
+                changes: Vec::new(),
+            };
+            for release in releases {
+                let user = match release.user {
+                    Some(ref user) => user.email.to_string(),
+                    None => "unknown".to_string(),
+                };
+                for change in history::changes_from_description(&release.description) {
+                    if let Some(k) = key {
+                        if !change.has_key(k) {
+                            continue;
+                        }
+                    }
+                    app_history.changes.push(output::HistoryChange {
+                        version: release.version,
+                        created_at: release.created_at.to_string(),
+                        user: user.to_string(),
+                        action: change.action,
+                        keys: change.keys,
+                    });
+                }
+            }
+            output.result(&app_history);
+        }
+        Err(platform_error) => output.platform_error(&platform_error),
+    }
+}
+
+/// Load a config file, warning when it uses an older version of the format
+///
+/// # Arguments
+///
+/// * `config_file_path` - The config file's path in the file system.
+///
+/// # Result
+///
+/// * `Result<Config, String>` - The configuration or an Error message
+///
+fn load_config(config_file_path: &str) -> Result<cfg::Config, String> {
+    let config = cfg::Config::from_path(config_file_path.to_string())?;
+    if config.is_outdated() {
+        eprintln!(
+            "Warning: {} is in version {} of the config file format, run `hke migrate -c {}` to upgrade it.",
+            config_file_path, config.version, config_file_path
+        );
+    }
+    Ok(config)
+}
+
+/// Load a config file, reading the settings given by a source directive
+///
+/// # Arguments
+///
+/// * `config_file_path` - The config file's path in the file system.
+///
+/// # Result
+///
+/// * `Result<Config, String>` - The configuration or an Error message for every setting that
+///   could not be read
+///
+fn load_resolved_config(config_file_path: &str) -> Result<cfg::Config, String> {
+    let mut config = load_config(config_file_path)?;
+    config.resolve_sources(config_file_path)?;
+    Ok(config)
+}
+
+/// Upgrade a config file to the latest version of the format
+///
+/// # Arguments
+///
+/// * `config_file_path` - The config file's path in the file system.
+/// * `output_path` - The path of the upgraded file, the config file itself if empty.
+/// * `force` - Overwrite the upgraded file if it already exists.
+/// * `output` - The output of the command
+///
+fn migrate(config_file_path: &str, output_path: &str, force: bool, output: &mut output::Output) {
+    let output_path = if output_path.is_empty() {
+        config_file_path
+    } else {
+        output_path
+    };
+    match cfg::Config::migrate(config_file_path.to_string(), output_path, force) {
+        Ok(s) => output.message(s),
+        Err(err) => output.error(err),
+    }
+}
+
+/// Validate a config file, reporting every problem found
+///
+/// # Arguments
+///
+/// * `config_file_path` - The config file's path in the file system.
+/// * `output` - The output of the command, failed if the config file is not valid
+///
+fn validate(config_file_path: &str, output: &mut output::Output) {
+    match cfg::Config::validate(config_file_path.to_string()) {
+        Ok(problems) => {
+            if !problems.is_empty() {
+                output.fail();
+            }
+            output.result(&output::Validation {
+                file: config_file_path.to_string(),
+                problems,
+            });
+        }
+        Err(err) => output.error(err),
+    }
+}
+
+/// Check the settings of a config file and the config vars of its apps against its schema
+///
+/// # Arguments
+///
+/// * `config_file_path` - The config file's path in the file system.
+/// * `local` - Only check the settings of the config file
+/// * `output` - The output of the command, failed if a config var is missing or malformed
+///
+fn check(config_file_path: &str, local: bool, output: &mut output::Output) {
+    let mut config = match load_resolved_config(config_file_path) {
+        Ok(config) => config,
+        Err(err) => return output.error(err),
+    };
+    if config.schema.is_empty() {
+        return output.error(format!("{} has no schema to check", config_file_path));
+    }
+    config.apply_defaults();
+
+    let mut client = if local { None } else { Some(heroku_client()) };
+    for app in &config.apps {
+        let mut app = app.clone();
+        let mut remote = Vec::new();
+        if let Some(ref mut client) = client {
+            match client.get_config_vars(app.name.to_string()) {
+                Ok(config_vars) => {
+                    let config_vars = config_vars_to_map(config_vars);
+                    remote = schema::check_config_vars(&config.schema, &config_vars);
+                    app.apply_schema_defaults(&config.schema, &config_vars);
+                }
+                Err(platform_error) => {
+                    output.platform_error(&platform_error);
+                    continue;
+                }
+            }
+        } else {
+            app.apply_schema_defaults(&config.schema, &BTreeMap::new());
+        }
+        let checked_app = output::CheckedApp {
+            name: app.name.to_string(),
+            local: schema::check_app(&config.schema, &app),
+            remote,
+        };
+        if !checked_app.is_valid() {
+            output.fail();
+        }
+        output.result(&checked_app);
+    }
+}
+
+/// Resolve the apps selected on the command line into app names
+///
+/// Glob patterns given with `--app` or `--match` are matched against the apps the user has
+/// access to, or against the apps of a team if `--team` is set. With `--team`, the app names
+/// must also be apps of the team.
+///
+/// # Arguments
+///
+/// * `matches` - List of command line arguments matchers (see clap documentation)
+///
+/// # Result
+///
+/// * `Result<Vec<String>, String>` - List of app names or an Error message
+///
+fn resolve_apps(matches: &clap::ArgMatches) -> Result<Vec<String>, String> {
+    let mut selectors: Vec<String> = Vec::new();
+    if let Some(names) = matches.values_of("app") {
+        selectors.extend(names.map(|name| name.to_string()));
+    }
+    if let Some(patterns) = matches.values_of("match") {
+        selectors.extend(patterns.map(|pattern| pattern.to_string()));
+    }
+
+    let team = matches.value_of("team");
+    if team.is_none() && !selectors.iter().any(|selector| apps::is_pattern(selector)) {
+        return apps::resolve(&selectors, &[]);
+    }
+
+    let mut client = heroku_client();
+    let available = match team {
+        Some(team) => client.list_team_apps(team.to_string()),
+        None => client.list_apps(),
+    };
+    match available {
+        Ok(app_names) => {
+            if let Some(team) = team {
+                apps::check_team(&selectors, team, &app_names)?;
+            }
+            apps::resolve(&selectors, &app_names)
+        }
+        Err(platform_error) => Err(format!(
+            "PlatformError: {}, {}",
+            platform_error.id, platform_error.message
+        )),
+    }
+}
+
+/// Command line option restricting the app patterns to the apps of a team
+fn team_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("team")
+        .short("t")
+        .long("team")
+        .value_name("TEAM")
+        .help("Team (or organization) to look for apps matching patterns")
+        .takes_value(true)
+}
+
+/// Command line option selecting every app matching a glob pattern
+fn match_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("match")
+        .short("m")
+        .long("match")
+        .value_name("PATTERN")
+        .help("Select every app matching a glob pattern (ex: 'api-*-staging')")
+        .multiple(true)
+        .number_of_values(1)
+        .takes_value(true)
+}
+
+/// Read the config vars selected with `--keys` and `--keys-match` on the command line
+///
+/// # Arguments
+///
+/// * `matches` - List of command line arguments matchers (see clap documentation)
+///
+/// # Result
+///
+/// * `Result<KeyFilter, String>` - The filter of the config vars or an Error message
+///
+fn key_filter(matches: &clap::ArgMatches) -> Result<keys::KeyFilter, String> {
+    let selected = values_of(matches, "keys");
+    let patterns = values_of(matches, "keys_match");
+    if selected.is_empty() && patterns.is_empty() {
+        return Ok(keys::KeyFilter::all());
+    }
+    keys::KeyFilter::new(&selected, &patterns)
+}
+
+/// Read the values of a command line option given multiple times
+///
+/// # Arguments
+///
+/// * `matches` - List of command line arguments matchers (see clap documentation)
+/// * `name` - The name of the option
+///
+fn values_of<'a>(matches: &'a clap::ArgMatches, name: &str) -> Vec<&'a str> {
+    match matches.values_of(name) {
+        Some(values) => values.collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Command line option allowing to overwrite an existing output file
+fn force_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("force")
+        .short("f")
+        .long("force")
+        .help("Overwrite the output file if it already exists")
+}
+
 /// Construct a Map of config vars (key-value pairs) from the command line arguments
 ///
-/// # Arguments
+/// # Arguments
 ///
 /// * `push_matches` - List of command line arguments matchers (see clap documentation)
 ///
 /// # Result
 ///
-/// * `HaspMap<String, String>` - Map of config vars (key-value pairs)
+/// * `Result<BTreeMap<String, String>, String>` - Map of config vars (key-value pairs), or an
+///   Error message if an argument is not in the KEY=VALUE format
 ///
-fn config_vars_from_args(push_matches: &clap::ArgMatches) -> HashMap<String, String> {
-    let mut settings: HashMap<String, String> = HashMap::new();
+fn config_vars_from_args(
+    push_matches: &clap::ArgMatches,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut settings: BTreeMap<String, String> = BTreeMap::new();
     if let Some(vars) = push_matches.values_of("vars") {
         for var in vars {
-            let key_value: Vec<&str> = var.split('=').collect();
-            settings.insert(key_value[0].to_string(), key_value[1].to_string());
+            let mut key_value = var.splitn(2, '=');
+            match (key_value.next(), key_value.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => {
+                    settings.insert(key.to_string(), value.to_string());
+                }
+                _ => return Err(format!("{} is not a config var, expected KEY=VALUE", var)),
+            }
         }
     }
+    Ok(settings)
+}
+
+/// Construct a Map of config vars (key-value pairs) from the config vars of an app
+///
+/// # Arguments
+///
+/// * `config_vars` - List of config vars in the KEY=VALUE format
+///
+/// # Result
+///
+/// * `BTreeMap<String, String>` - Map of config vars (key-value pairs)
+///
+fn config_vars_to_map(config_vars: Vec<String>) -> BTreeMap<String, String> {
+    let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    for var in config_vars {
+        let mut key_value = var.splitn(2, '=');
+        let key = key_value.next().unwrap_or("");
+        let value = key_value.next().unwrap_or("");
+        settings.insert(key.to_string(), value.to_string());
+    }
     settings
 }
 
+/// Construct a Map of config vars (key-value pairs) from a file in the .env format
+///
+/// # Arguments
+///
+/// * `path` - The path of the env file
+///
+/// # Result
+///
+/// * `Result<BTreeMap<String, String>, String>` - Map of config vars or an Error message
+///
+fn config_vars_from_env_file(path: &str) -> Result<BTreeMap<String, String>, String> {
+    import::from_dotenv(path)
+}
+
+/// Read how long to wait for the releases created by an update, from the command line arguments
+///
+/// # Arguments
+///
+/// * `matches` - List of command line arguments matchers (see clap documentation)
+///
+/// # Result
+///
+/// * `Option<Duration>` - The maximum time to wait for a release, `None` without `--wait`
+///
+fn wait_timeout(matches: &clap::ArgMatches) -> Option<Duration> {
+    if !matches.is_present("wait") {
+        return None;
+    }
+    let seconds = matches
+        .value_of("wait_timeout")
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_WAIT_TIMEOUT);
+    Some(Duration::from_secs(seconds))
+}
+
+/// Check that a command line argument is a number of seconds (see clap validators)
+///
+/// # Arguments
+///
+/// * `value` - The value of the argument
+///
+/// # Result
+///
+/// * `Result<(), String>` - An error message if it is not a number of seconds
+///
+fn is_number_of_seconds(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a number of seconds", value))
+}
+
 /// Intialize an Heroku Platform API Client
 ///
 /// # Result
@@ -264,36 +1788,495 @@ fn heroku_client() -> platform_api::PlatformAPI {
 
 /// Lauch the update of config vars for every app in the config file.
 ///
-/// # Arguments
+/// # Arguments
 ///
 /// * `config` - Config Struct containing the settings structure.
+/// * `options` - Options of the push command.
+/// * `output` - The output of the command, failed if one of the releases has failed
 ///
-fn update_config_vars(config: cfg::Config) {
+/// # Result
+///
+/// * `Vec<String>` - The names of the apps whose config vars were updated
+///
+fn update_config_vars(
+    config: cfg::Config,
+    options: &PushOptions,
+    output: &mut output::Output,
+) -> Vec<String> {
     let mut client = heroku_client();
+    let mut updated: Vec<String> = Vec::new();
 
-    for app in config.apps {
-        if app.settings.is_empty() {
-            println!(
+    // The apps changing config vars of add-ons are refused before asking for any confirmation
+    let mut checked: Vec<cfg::App> = Vec::new();
+    for app in apps_with_schema_defaults(&mut client, config, output) {
+        if !options.force_addon_vars && !app.is_empty() {
+            match addon_managed_vars(&mut client, &app) {
+                Ok(ref keys) if keys.is_empty() => {}
+                Ok(keys) => {
+                    output.error(addon_vars_refusal(&app.name, &keys));
+                    continue;
+                }
+                Err(platform_error) => {
+                    output.platform_error(&platform_error);
+                    return updated;
+                }
+            }
+        }
+        checked.push(app);
+    }
+
+    let apps = confirm_apps(&mut client, checked, options, output);
+    for app in apps {
+        if app.is_empty() {
+            output.message(format!(
                 "Skipping update for app {}, no settings were found.",
                 app.name
-            );
-        } else {
-            match client.set_config_vars(app.name.to_string(), app.settings) {
-                Ok(config_vars) => {
-                    println!("{}", app.name);
-                    for arg in config_vars {
-                        println!("{}", arg);
+            ));
+            continue;
+        }
+        if !patch_app(&mut client, &app.name, app.changes(), options.wait, output) {
+            break;
+        }
+        updated.push(app.name);
+    }
+    updated
+}
+
+/// Add the default values of the schema of a config file missing from the apps on heroku
+///
+/// # Arguments
+///
+/// * `client` - The heroku Platform API client
+/// * `config` - The changes of every app, with the schema of the config file
+/// * `output` - The output of the command, failed if the config vars of an app could not be read
+///
+/// # Result
+///
+/// * `Vec<App>` - The apps with the default values to push, without the apps whose config vars
+///   could not be read
+///
+fn apps_with_schema_defaults(
+    client: &mut platform_api::PlatformAPI,
+    config: cfg::Config,
+    output: &mut output::Output,
+) -> Vec<cfg::App> {
+    if !config.schema.values().any(|rule| rule.default.is_some()) {
+        return config.apps;
+    }
+    let mut apps: Vec<cfg::App> = Vec::new();
+    for mut app in config.apps {
+        match client.get_config_vars(app.name.to_string()) {
+            Ok(config_vars) => {
+                app.apply_schema_defaults(&config.schema, &config_vars_to_map(config_vars));
+                apps.push(app);
+            }
+            Err(platform_error) => output.platform_error(&platform_error),
+        }
+    }
+    apps
+}
+
+/// Push the changes of a config file every time it is saved, until the command is interrupted
+///
+/// The config file is loaded again on every change, and only the config vars changed since the
+/// last push of each app are pushed. An app that could not be updated is pushed again on the
+/// next change. The default values of the schema are only pushed by the first push.
+///
+/// # Arguments
+///
+/// * `push_matches` - List of command line arguments matchers (see clap documentation)
+/// * `config` - The changes of every app, pushed first
+/// * `options` - Options of the push command
+/// * `output` - The output of the command
+///
+fn watch(
+    push_matches: &clap::ArgMatches,
+    config: cfg::Config,
+    options: &PushOptions,
+    output: &mut output::Output,
+) {
+    let config_file_path = push_matches.value_of("config").unwrap_or_default();
+    let mut watcher = watch::Watcher::new(config_file_path);
+    let mut applied: BTreeMap<String, BTreeMap<String, Option<String>>> = BTreeMap::new();
+    let mut pending = config;
+    loop {
+        let changes: BTreeMap<String, BTreeMap<String, Option<String>>> = pending
+            .apps
+            .iter()
+            .map(|app| (app.name.to_string(), app.changes()))
+            .collect();
+        for name in update_config_vars(pending, options, output) {
+            if let Some(app_changes) = changes.get(&name) {
+                applied.entry(name).or_default().extend(app_changes.clone());
+            }
+        }
+
+        output.progress(&format!(
+            "Watching {} for changes, press Ctrl-C to stop.",
+            config_file_path
+        ));
+        pending = cfg::Config::new();
+        while pending.apps.is_empty() {
+            watcher.wait_for_change();
+            let config = match config_from_sources(push_matches) {
+                Ok(config) => config,
+                Err(err) => {
+                    // The file is fixed by the next save, the command goes on
+                    eprintln!("Error: {}", err);
+                    continue;
+                }
+            };
+            let no_changes = BTreeMap::new();
+            for app in config.apps {
+                let delta = watch::delta(
+                    applied.get(&app.name).unwrap_or(&no_changes),
+                    &app.changes(),
+                );
+                if delta.is_empty() {
+                    continue;
+                }
+                let mut changed_app = cfg::App::new();
+                changed_app.name = app.name;
+                changed_app.protected = app.protected;
+                for (key, value) in delta {
+                    match value {
+                        Some(value) => {
+                            changed_app.settings.insert(key, value);
+                        }
+                        None => changed_app.unset.push(key),
+                    }
+                }
+                pending.apps.push(changed_app);
+            }
+            if pending.apps.is_empty() {
+                output.progress(&format!("{} changed, nothing to push.", config_file_path));
+            }
+        }
+    }
+}
+
+/// Error message refusing to update config vars managed by add-ons
+///
+/// # Arguments
+///
+/// * `app_name` - The name of the app
+/// * `keys` - The keys of the config vars managed by add-ons
+///
+fn addon_vars_refusal(app_name: &str, keys: &[String]) -> String {
+    format!(
+        "refusing to update config vars managed by add-ons on app {}: {}. \
+         Use --force-addon-vars to update them anyway.",
+        app_name,
+        keys.join(", ")
+    )
+}
+
+/// Update the config vars of an app in a single release, and report the release
+///
+/// # Arguments
+///
+/// * `client` - The heroku Platform API client
+/// * `app_name` - The name of the app
+/// * `changes` - The new value of each config var, `None` to remove it
+/// * `wait` - Maximum time to wait for the release to succeed or fail, `None` to not wait
+/// * `output` - The output of the command, failed if the release has failed
+///
+/// # Result
+///
+/// * `bool` - false if the config vars could not be updated
+///
+fn patch_app(
+    client: &mut platform_api::PlatformAPI,
+    app_name: &str,
+    changes: BTreeMap<String, Option<String>>,
+    wait: Option<Duration>,
+    output: &mut output::Output,
+) -> bool {
+    let previous_version = match client.get_latest_release(app_name.to_string()) {
+        Ok(release) => Some(release.version),
+        Err(platform_error) => {
+            output.message(format!(
+                "Could not fetch the latest release of app {} before the update, \
+                 the number of releases created is unknown: {}",
+                app_name, platform_error.message
+            ));
+            None
+        }
+    };
+    let keys: Vec<String> = changes.keys().cloned().collect();
+    match client.patch_config_vars(app_name.to_string(), changes) {
+        Ok(config_vars) => {
+            let update = release_of_update(client, app_name, previous_version, &keys, wait, output);
+            let release = match update {
+                Ok(None) => {
+                    output.message(format!(
+                        "No release of app {} matches the update of its config vars, \
+                         the release it created is unknown",
+                        app_name
+                    ));
+                    None
+                }
+                Ok(Some(release)) => {
+                    if release.is_failed() {
+                        output.fail();
                     }
-                    println!("-------------------------");
+                    Some(output::ReleaseInfo {
+                        count: previous_version
+                            .map(|previous| release.version.saturating_sub(previous)),
+                        version: release.version,
+                        status: release.status,
+                        description: release.description,
+                    })
                 }
                 Err(platform_error) => {
-                    println!(
-                        "PlatformError: {}, {}",
-                        platform_error.id, platform_error.message
-                    );
-                    break;
+                    output.platform_error(&platform_error);
+                    None
                 }
+            };
+            output.result(&output::UpdatedApp {
+                name: app_name.to_string(),
+                config_vars: config_vars_to_map(config_vars),
+                release,
+            });
+            true
+        }
+        Err(platform_error) => {
+            output.platform_error(&platform_error);
+            false
+        }
+    }
+}
+
+/// Show the changes of the config vars of every app and ask the user to confirm them
+///
+/// Nothing is asked with `--yes` or when the standard input is not a terminal, except for
+/// protected apps: their name must always be typed, so they are never updated by a script.
+///
+/// # Arguments
+///
+/// * `client` - The heroku Platform API client
+/// * `apps` - The apps to update
+/// * `options` - Options of the push command
+/// * `output` - The output of the command, to show the changes
+///
+/// # Result
+///
+/// * `Vec<App>` - The apps whose changes were confirmed
+///
+fn confirm_apps(
+    client: &mut platform_api::PlatformAPI,
+    apps: Vec<cfg::App>,
+    options: &PushOptions,
+    output: &mut output::Output,
+) -> Vec<cfg::App> {
+    let interactive = confirm::is_interactive();
+    let mut confirmed: Vec<cfg::App> = Vec::new();
+    let mut to_confirm: Vec<cfg::App> = Vec::new();
+    for app in apps {
+        if app.is_empty() || (!app.protected && (options.yes || !interactive)) {
+            confirmed.push(app);
+        } else if !interactive {
+            output.error(format!(
+                "app {} is protected, its name must be typed to confirm the push in a terminal",
+                app.name
+            ));
+        } else {
+            to_confirm.push(app);
+        }
+    }
+    if to_confirm.is_empty() {
+        return confirmed;
+    }
+
+    let mut planned: Vec<cfg::App> = Vec::new();
+    for app in to_confirm {
+        match client.get_config_vars(app.name.to_string()) {
+            Ok(config_vars) => {
+                let planned_app = output::PlannedApp {
+                    name: app.name.to_string(),
+                    protected: app.protected,
+                    changes: plan::changes(&config_vars_to_map(config_vars), &app),
+                };
+                output.progress(planned_app.to_string().trim_end());
+                planned.push(app);
             }
+            Err(platform_error) => output.platform_error(&platform_error),
         }
     }
+
+    let unprotected = planned.iter().filter(|app| !app.protected).count();
+    let answer = match unprotected {
+        0 => confirm::Answer::Nothing,
+        1 => confirm::Answer::from_answer(&ask("Push these changes? [y/N] ", output)),
+        count => confirm::Answer::from_answer(&ask(
+            &format!(
+                "Push these changes to the {} apps? [y/N/s to select the apps] ",
+                count
+            ),
+            output,
+        )),
+    };
+    for app in planned {
+        let accepted = if app.protected {
+            let question = format!(
+                "{} is protected, type its name to push the changes: ",
+                app.name
+            );
+            ask(&question, output) == app.name
+        } else {
+            match answer {
+                confirm::Answer::All => true,
+                confirm::Answer::Nothing => false,
+                confirm::Answer::Select => confirm::is_yes(&ask(
+                    &format!("Push the changes to {}? [y/N] ", app.name),
+                    output,
+                )),
+            }
+        };
+        if accepted {
+            confirmed.push(app);
+        } else {
+            output.message(format!(
+                "Skipping update for app {}, the changes were not confirmed.",
+                app.name
+            ));
+        }
+    }
+    confirmed
+}
+
+/// Ask a question to the user
+///
+/// # Arguments
+///
+/// * `question` - The question to ask
+/// * `output` - The output of the command, failed if the answer could not be read
+///
+/// # Result
+///
+/// * `String` - The answer, empty if it could not be read
+///
+fn ask(question: &str, output: &mut output::Output) -> String {
+    match confirm::ask(question) {
+        Ok(answer) => answer,
+        Err(err) => {
+            output.error(err.to_string());
+            String::new()
+        }
+    }
+}
+
+/// Fetch the release created by an update of the config vars of an app
+///
+/// # Arguments
+///
+/// * `client` - The heroku Platform API client
+/// * `app_name` - The name of the updated app
+/// * `previous_version` - The version of the latest release before the update, if known
+/// * `keys` - The keys of the updated config vars
+/// * `wait` - Maximum time to poll the release until it is not pending anymore, `None` to not
+///   poll it
+/// * `output` - The output of the command, to report the progress
+///
+/// # Result
+///
+/// * `Result<Option<Release>, PlatformError>` - The release of the update, `None` if no release
+///   matches it, or PlatformError struct when it is still pending after the maximum time
+///
+fn release_of_update(
+    client: &mut platform_api::PlatformAPI,
+    app_name: &str,
+    previous_version: Option<u64>,
+    keys: &[String],
+    wait: Option<Duration>,
+    output: &output::Output,
+) -> Result<Option<platform_api::Release>, platform_api::PlatformError> {
+    let releases = client.get_latest_releases(app_name.to_string())?;
+    let mut release = match find_update_release(releases, previous_version, keys) {
+        Some(release) => release,
+        None => return Ok(None),
+    };
+    let timeout = match wait {
+        Some(timeout) if release.is_pending() => timeout,
+        _ => return Ok(Some(release)),
+    };
+    output.progress(&format!(
+        "Waiting for release v{} of {}...",
+        release.version, app_name
+    ));
+    let started = Instant::now();
+    while release.is_pending() {
+        if started.elapsed() >= timeout {
+            return Err(platform_api::PlatformError {
+                id: "wait_timeout".to_string(),
+                message: format!(
+                    "release v{} of app {} is still pending after {} seconds, \
+                     use --wait-timeout to wait longer",
+                    release.version,
+                    app_name,
+                    timeout.as_secs()
+                ),
+            });
+        }
+        thread::sleep(Duration::from_secs(RELEASE_POLL_INTERVAL));
+        release = client.get_release(app_name.to_string(), release.id)?;
+    }
+    Ok(Some(release))
+}
+
+/// Find the release created by an update of config vars among the latest releases of an app
+///
+/// Heroku does not tell which release an update created: the release must be newer than the
+/// one before the update, and its description must only change the updated config vars, so
+/// a concurrent deploy or update of other config vars is not mistaken for it.
+///
+/// # Arguments
+///
+/// * `releases` - The latest releases of the app, from the latest to the oldest
+/// * `previous_version` - The version of the latest release before the update, if known
+/// * `keys` - The keys of the updated config vars
+///
+/// # Result
+///
+/// * `Option<Release>` - The latest matching release, `None` if no release matches
+///
+fn find_update_release(
+    releases: Vec<platform_api::Release>,
+    previous_version: Option<u64>,
+    keys: &[String],
+) -> Option<platform_api::Release> {
+    releases.into_iter().find(|release| {
+        let is_newer = match previous_version {
+            Some(previous) => release.version > previous,
+            None => true,
+        };
+        is_newer && history::is_update_of(&release.description, keys)
+    })
+}
+
+/// List the settings of an app that are config vars managed by add-ons
+///
+/// # Arguments
+///
+/// * `client` - The heroku Platform API client
+/// * `app` - The app to check
+///
+/// # Result
+///
+/// * `Result<Vec<String>, PlatformError>` - Names of the add-on config vars, or PlatformError
+///
+fn addon_managed_vars(
+    client: &mut platform_api::PlatformAPI,
+    app: &cfg::App,
+) -> Result<Vec<String>, platform_api::PlatformError> {
+    let attachments = client.get_addon_attachments(app.name.to_string())?;
+    let mut keys: Vec<String> = app
+        .settings
+        .keys()
+        .chain(app.unset.iter())
+        .filter(|key| attachments.iter().any(|a| a.manages(key)))
+        .cloned()
+        .collect();
+    keys.sort();
+    Ok(keys)
 }
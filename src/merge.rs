@@ -0,0 +1,506 @@
+//! Merge the settings of apps into the content of an existing config file
+//!
+//! Like migrations, the file is edited in place rather than serialized again: the other apps,
+//! the order of the keys and the comments are kept. Only the settings that changed are
+//! rewritten, settings missing from an app are removed and new ones are added after the last
+//! setting of the app. Apps missing from the file are added at the end of the list of apps.
+//! Settings read from a source directive or from Vault are never replaced by their values.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let mut app = App::new();
+//! app.name = String::from("my-app");
+//! app.settings.insert(String::from("MY_VAR"), String::from("2"));
+//! let yaml = "version: \"2\"\napps:\n  - name: \"my-app\" # production\n    settings:\n      MY_VAR: \"1\"\n";
+//! let merged = merge::merge_apps(yaml, &[app], &KeyFilter::all()).unwrap();
+//! assert_eq!(merged, "version: \"2\"\napps:\n  - name: \"my-app\" # production\n    settings:\n      MY_VAR: \"2\"\n");
+//! ```
+use std::collections::HashMap;
+
+use serde_json;
+
+use config::App;
+use keys::KeyFilter;
+use sources::VAULT_PREFIX;
+use yaml::{self, Node};
+
+/// Replacement of a range of lines of the file
+struct Edit {
+    /// Index of the first line to replace
+    start: usize,
+    /// Index of the line after the last line to replace, `start` to insert lines
+    end: usize,
+    /// The new lines
+    lines: Vec<String>,
+}
+
+impl Edit {
+    fn replace(start: usize, last: usize, lines: Vec<String>) -> Self {
+        Edit {
+            start,
+            end: last + 1,
+            lines,
+        }
+    }
+
+    fn insert(start: usize, lines: Vec<String>) -> Self {
+        Edit {
+            start,
+            end: start,
+            lines,
+        }
+    }
+}
+
+/// Merge the settings of apps into the content of a config file
+///
+/// The settings of an app already in the file are replaced by the ones given, except those
+/// inherited with the same value from the `defaults` of the file. Only the settings selected
+/// by the filter are removed from an app when they are not given.
+///
+/// # Arguments
+/// * `yaml` - The content of a config file in YAML format
+/// * `apps` - The apps to merge into the file
+/// * `keys` - The keys of the settings given for the apps
+///
+/// # Result
+/// * `Result<String, String>` - The merged content or an Error message
+///
+pub fn merge_apps(yaml: &str, apps: &[App], keys: &KeyFilter) -> Result<String, String> {
+    let root = match yaml::parse(yaml) {
+        Ok(Some(root)) => root,
+        Ok(None) => return Err("The config file is empty".to_string()),
+        Err(err) => return Err(format!("{}: {}", err.position, err.message)),
+    };
+    let (apps_key, apps_node) = match root {
+        Node::Mapping(ref pairs, _) => match pairs.iter().find(|p| p.0.as_str() == Some("apps")) {
+            Some((key, value)) => (key, value),
+            None => return Err("missing field `apps`".to_string()),
+        },
+        _ => return Err(format!("{}: expected a map", root.position())),
+    };
+    let mut defaults: HashMap<&str, &str> = HashMap::new();
+    if let Some(Node::Mapping(pairs, _)) = root.get("defaults") {
+        for (key, value) in pairs {
+            if let (Some(key), Some(value)) = (key.as_str(), value.as_str()) {
+                defaults.insert(key, value);
+            }
+        }
+    }
+
+    let lines: Vec<String> = yaml.split('\n').map(|line| line.to_string()).collect();
+    // Only a list in the block style can be edited, any other value is an empty list
+    let items: &[Node] = match *apps_node {
+        Node::Sequence(ref items, _) => match items.first() {
+            Some(first) if first.position().line > apps_key.position().line => items,
+            _ => &[],
+        },
+        _ => &[],
+    };
+
+    let mut edits: Vec<Edit> = Vec::new();
+    let mut new_apps: Vec<String> = Vec::new();
+    for app in apps {
+        let item = items.iter().find(|item| {
+            item.get("name").and_then(|name| name.as_str()) == Some(app.name.as_str())
+        });
+        let settings: Vec<(&String, &String)> = app
+            .settings
+            .iter()
+            .filter(|&(key, value)| {
+                let inherited = defaults.get(key.as_str()) == Some(&value.as_str());
+                let overridden = match item.and_then(|item| item.get("settings")) {
+                    Some(settings) => settings.get(key).is_some(),
+                    None => false,
+                };
+                !inherited || overridden
+            })
+            .collect();
+
+        match item {
+            Some(item) => edits.extend(app_edits(&lines, item, &settings, keys)),
+            None => {
+                let dash_indent = match items.last() {
+                    Some(last) => (last.position().column - 1).saturating_sub(2),
+                    None => indent_of(&lines[apps_key.position().line - 1]) + 2,
+                };
+                new_apps.extend(render_app(&app.name, &settings, dash_indent));
+            }
+        }
+    }
+
+    if !new_apps.is_empty() {
+        match items.last() {
+            Some(last) => {
+                let end = item_end(&lines, last);
+                edits.push(Edit::insert(end + 1, new_apps));
+            }
+            None => {
+                let start = apps_key.position().line - 1;
+                let end = block_end(&lines, start, apps_key.position().column - 1);
+                let mut block = vec![format!(
+                    "{}apps:",
+                    " ".repeat(apps_key.position().column - 1)
+                )];
+                block.extend(new_apps);
+                edits.push(Edit::replace(start, end, block));
+            }
+        }
+    }
+
+    Ok(apply(lines, edits).join("\n"))
+}
+
+/// Remove settings and removals of an app from the content of a config file
+///
+/// Settings read from a source directive and removals in `unset` are kept by `merge_apps`, they
+/// are removed first when config vars replace them. Only settings and removals in the block
+/// style are removed, an `unset` list left empty is removed as a whole.
+///
+/// # Arguments
+/// * `yaml` - The content of a config file in YAML format
+/// * `app_name` - The name of the app
+/// * `keys` - The keys of the settings and removals to remove
+///
+/// # Result
+/// * `Result<String, String>` - The content without the keys or an Error message
+///
+pub fn remove_keys(yaml: &str, app_name: &str, keys: &[String]) -> Result<String, String> {
+    let root = match yaml::parse(yaml) {
+        Ok(Some(root)) => root,
+        Ok(None) => return Err("The config file is empty".to_string()),
+        Err(err) => return Err(format!("{}: {}", err.position, err.message)),
+    };
+    let item = match root.get("apps") {
+        Some(Node::Sequence(items, _)) => items
+            .iter()
+            .find(|item| item.get("name").and_then(|name| name.as_str()) == Some(app_name)),
+        _ => None,
+    };
+    let pairs = match item {
+        Some(Node::Mapping(pairs, _)) => pairs,
+        _ => return Ok(yaml.to_string()),
+    };
+
+    let lines: Vec<String> = yaml.split('\n').map(|line| line.to_string()).collect();
+    let removed = |key: &Node| match key.as_str() {
+        Some(key) => keys.iter().any(|k| k == key),
+        None => false,
+    };
+    let mut edits: Vec<Edit> = Vec::new();
+    for (key, value) in pairs {
+        let block = value.position().line > key.position().line;
+        match (key.as_str(), value) {
+            (Some("settings"), Node::Mapping(settings, _)) if block => {
+                for (setting_key, _) in settings.iter().filter(|pair| removed(&pair.0)) {
+                    let start = setting_key.position().line - 1;
+                    let end = block_end(&lines, start, setting_key.position().column - 1);
+                    edits.push(Edit::replace(start, end, Vec::new()));
+                }
+            }
+            (Some("unset"), Node::Sequence(unset, _)) if block => {
+                if unset.iter().all(&removed) {
+                    let start = key.position().line - 1;
+                    let end = block_end(&lines, start, key.position().column - 1);
+                    edits.push(Edit::replace(start, end, Vec::new()));
+                    continue;
+                }
+                for unset_key in unset.iter().filter(|k| removed(k)) {
+                    let start = unset_key.position().line - 1;
+                    edits.push(Edit::replace(
+                        start,
+                        item_end(&lines, unset_key),
+                        Vec::new(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(apply(lines, edits).join("\n"))
+}
+
+/// Edits of the settings of an app already in the file
+fn app_edits(
+    lines: &[String],
+    item: &Node,
+    settings: &[(&String, &String)],
+    keys: &KeyFilter,
+) -> Vec<Edit> {
+    let mut edits: Vec<Edit> = Vec::new();
+    let settings_pair = match *item {
+        Node::Mapping(ref pairs, _) => pairs.iter().find(|p| p.0.as_str() == Some("settings")),
+        _ => None,
+    };
+    let key_indent = item.position().column - 1;
+
+    match settings_pair {
+        // Settings in the block style, edited setting by setting
+        Some((key, Node::Mapping(pairs, position))) if position.line > key.position().line => {
+            let indent = position.column - 1;
+            let mut last_end = key.position().line - 1;
+            for (setting_key, value) in pairs {
+                let start = setting_key.position().line - 1;
+                let end = block_end(lines, start, setting_key.position().column - 1);
+                last_end = end;
+                if is_source(value) {
+                    // The value pulled comes from the source
+                    continue;
+                }
+                let name = setting_key.as_str().unwrap_or("");
+                match settings.iter().find(|s| s.0 == name) {
+                    Some(setting) if value.as_str() != Some(setting.1.as_str()) => edits.push(
+                        Edit::replace(start, end, vec![render_setting(setting, indent)]),
+                    ),
+                    Some(_) => {}
+                    None if keys.matches(name) => edits.push(Edit::replace(start, end, Vec::new())),
+                    None => {}
+                }
+            }
+            let added: Vec<String> = settings
+                .iter()
+                .filter(|s| pairs.iter().all(|p| p.0.as_str() != Some(s.0.as_str())))
+                .map(|s| render_setting(s, indent))
+                .collect();
+            if !added.is_empty() {
+                edits.push(Edit::insert(last_end + 1, added));
+            }
+        }
+        // Settings in the flow style or empty, replaced as a whole
+        Some((key, value))
+            if (keys.is_all() && !has_sources(value)) || value.is_null() || is_empty_map(value) =>
+        {
+            let start = key.position().line - 1;
+            let end = block_end(lines, start, key.position().column - 1);
+            edits.push(Edit::replace(
+                start,
+                end,
+                render_settings(settings, key.position().column - 1),
+            ));
+        }
+        // Settings in the flow style with other keys or source directives, kept as they are
+        Some(_) => {}
+        None => {
+            let end = item_end(lines, item);
+            edits.push(Edit::insert(end + 1, render_settings(settings, key_indent)));
+        }
+    }
+    edits
+}
+
+/// Check if a node is a map without any key
+fn is_empty_map(node: &Node) -> bool {
+    match *node {
+        Node::Mapping(ref pairs, _) => pairs.is_empty(),
+        _ => false,
+    }
+}
+
+/// Check if a map of settings has sources, or any other value than a scalar
+fn has_sources(node: &Node) -> bool {
+    match *node {
+        Node::Mapping(ref pairs, _) => pairs.iter().any(|pair| is_source(&pair.1)),
+        _ => false,
+    }
+}
+
+/// Check if the value of a setting is a source directive or a reference to a Vault secret
+fn is_source(node: &Node) -> bool {
+    match node.as_str() {
+        Some(value) => value.starts_with(VAULT_PREFIX),
+        None => true,
+    }
+}
+
+/// Apply the edits to the lines of the file, from the last one so that line indexes stay valid
+fn apply(mut lines: Vec<String>, mut edits: Vec<Edit>) -> Vec<String> {
+    edits.sort_by_key(|edit| (edit.start, edit.end));
+    for edit in edits.into_iter().rev() {
+        let end = edit.end.min(lines.len());
+        let start = edit.start.min(end);
+        lines.splice(start..end, edit.lines);
+    }
+    lines
+}
+
+/// Index of the last line of an item of a list
+fn item_end(lines: &[String], item: &Node) -> usize {
+    let position = item.position();
+    block_end(
+        lines,
+        position.line - 1,
+        (position.column - 1).saturating_sub(1),
+    )
+}
+
+/// Index of the last line of a block starting at a line, made of the following lines more
+/// indented than `indent`. Blank lines and comments after the block are not part of it.
+fn block_end(lines: &[String], start: usize, indent: usize) -> usize {
+    let mut end = start;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        let content = line.trim();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        if indent_of(line) <= indent {
+            break;
+        }
+        end = index;
+    }
+    end
+}
+
+/// Number of spaces at the start of a line
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Lines of a new app of the list of apps
+fn render_app(name: &str, settings: &[(&String, &String)], dash_indent: usize) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{}- name: {}",
+        " ".repeat(dash_indent),
+        quote(name)
+    )];
+    lines.extend(render_settings(settings, dash_indent + 2));
+    lines
+}
+
+/// Lines of the settings of an app
+fn render_settings(settings: &[(&String, &String)], indent: usize) -> Vec<String> {
+    if settings.is_empty() {
+        return vec![format!("{}settings: {{}}", " ".repeat(indent))];
+    }
+    let mut lines = vec![format!("{}settings:", " ".repeat(indent))];
+    lines.extend(settings.iter().map(|s| render_setting(s, indent + 2)));
+    lines
+}
+
+/// Line of a setting of an app
+fn render_setting(setting: &(&String, &String), indent: usize) -> String {
+    format!("{}{}: {}", " ".repeat(indent), setting.0, quote(setting.1))
+}
+
+/// Quote a value, a JSON string being a valid double-quoted YAML scalar
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("\"{}\"", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(name: &str, settings: &[(&str, &str)]) -> App {
+        let mut app = App::new();
+        app.name = name.to_string();
+        for &(key, value) in settings {
+            app.settings.insert(key.to_string(), value.to_string());
+        }
+        app
+    }
+
+    #[test]
+    fn should_update_settings_of_existing_apps() {
+        let yaml = "version: \"2\"\n# My apps\napps:\n  - name: \"my-app\" # production\n    settings:\n      # Database\n      OLD_VAR: \"1\"\n      MY_VAR: \"1\"\n      SAME_VAR: same # kept\n  - name: \"my-other-app\"\n    settings:\n      MY_VAR: \"1\"\n";
+        let pulled = app(
+            "my-app",
+            &[
+                ("MY_VAR", "2"),
+                ("SAME_VAR", "same"),
+                ("NEW_VAR", "a \"b\""),
+            ],
+        );
+        assert_eq!(
+            merge_apps(yaml, &[pulled], &KeyFilter::all()).unwrap(),
+            "version: \"2\"\n# My apps\napps:\n  - name: \"my-app\" # production\n    settings:\n      # Database\n      MY_VAR: \"2\"\n      SAME_VAR: same # kept\n      NEW_VAR: \"a \\\"b\\\"\"\n  - name: \"my-other-app\"\n    settings:\n      MY_VAR: \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn should_add_new_apps() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      MY_VAR: \"1\"\n\n# The end\n";
+        let pulled = app("my-new-app", &[("MY_VAR", "2")]);
+        assert_eq!(
+            merge_apps(yaml, &[pulled], &KeyFilter::all()).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      MY_VAR: \"1\"\n  - name: \"my-new-app\"\n    settings:\n      MY_VAR: \"2\"\n\n# The end\n"
+        );
+
+        let yaml = "version: \"2\"\napps: []\n";
+        let pulled = app("my-new-app", &[]);
+        assert_eq!(
+            merge_apps(yaml, &[pulled], &KeyFilter::all()).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-new-app\"\n    settings: {}\n"
+        );
+    }
+
+    #[test]
+    fn should_replace_settings_not_in_block_style() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings: { MY_VAR: \"1\" }\n    unset:\n      - OLD_VAR\n  - name: \"my-other-app\"\n";
+        let pulled = vec![
+            app("my-app", &[("MY_VAR", "2")]),
+            app("my-other-app", &[("MY_VAR", "3")]),
+        ];
+        assert_eq!(
+            merge_apps(yaml, &pulled, &KeyFilter::all()).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      MY_VAR: \"2\"\n    unset:\n      - OLD_VAR\n  - name: \"my-other-app\"\n    settings:\n      MY_VAR: \"3\"\n"
+        );
+    }
+
+    #[test]
+    fn should_only_remove_filtered_settings() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      AWS_KEY: \"1\"\n      AWS_REGION: \"eu\"\n      MY_VAR: \"1\"\n";
+        let pulled = app("my-app", &[("AWS_KEY", "2")]);
+        let keys = KeyFilter::new(&[], &["^AWS_"]).unwrap();
+        assert_eq!(
+            merge_apps(yaml, &[pulled], &keys).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      AWS_KEY: \"2\"\n      MY_VAR: \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn should_keep_source_directives() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      STRIPE_KEY: { file: ./stripe.key }\n      DATABASE_URL: \"vault://secret/data/my-app#DATABASE_URL\"\n      MY_VAR: \"1\"\n  - name: \"my-other-app\"\n    settings: { TOKEN: { env: TOKEN } }\n";
+        let pulled = vec![
+            app(
+                "my-app",
+                &[
+                    ("STRIPE_KEY", "sk_live"),
+                    ("DATABASE_URL", "postgres://db"),
+                    ("MY_VAR", "2"),
+                ],
+            ),
+            app("my-other-app", &[("TOKEN", "secret")]),
+        ];
+        assert_eq!(
+            merge_apps(yaml, &pulled, &KeyFilter::all()).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      STRIPE_KEY: { file: ./stripe.key }\n      DATABASE_URL: \"vault://secret/data/my-app#DATABASE_URL\"\n      MY_VAR: \"2\"\n  - name: \"my-other-app\"\n    settings: { TOKEN: { env: TOKEN } }\n"
+        );
+    }
+
+    #[test]
+    fn should_remove_settings_and_removals() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\" # production\n    settings:\n      STRIPE_KEY: { file: ./stripe.key }\n      MY_VAR: \"1\"\n    unset:\n      - OLD_VAR\n      - TOKEN\n  - name: \"my-other-app\"\n    unset:\n      - TOKEN\n";
+        let keys = vec!["STRIPE_KEY".to_string(), "TOKEN".to_string()];
+        assert_eq!(
+            remove_keys(yaml, "my-app", &keys).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-app\" # production\n    settings:\n      MY_VAR: \"1\"\n    unset:\n      - OLD_VAR\n  - name: \"my-other-app\"\n    unset:\n      - TOKEN\n"
+        );
+
+        let keys = vec!["TOKEN".to_string()];
+        assert_eq!(
+            remove_keys(yaml, "my-other-app", &keys).unwrap(),
+            "version: \"2\"\napps:\n  - name: \"my-app\" # production\n    settings:\n      STRIPE_KEY: { file: ./stripe.key }\n      MY_VAR: \"1\"\n    unset:\n      - OLD_VAR\n      - TOKEN\n  - name: \"my-other-app\"\n"
+        );
+    }
+
+    #[test]
+    fn should_not_repeat_defaults() {
+        let yaml = "version: \"2\"\ndefaults:\n  LOG_LEVEL: \"info\"\napps:\n  - name: \"my-app\"\n    settings:\n      MY_VAR: \"1\"\n";
+        let pulled = app("my-app", &[("MY_VAR", "1"), ("LOG_LEVEL", "info")]);
+        assert_eq!(
+            merge_apps(yaml, &[pulled], &KeyFilter::all()).unwrap(),
+            yaml
+        );
+    }
+}
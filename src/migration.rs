@@ -0,0 +1,152 @@
+//! Upgrade config files to the latest version of the configuration format
+//!
+//! Each migration upgrades a file from one version to the next one. Files are edited in place
+//! rather than serialized again, to keep their comments, order and formatting.
+//!
+//! Changes of the version 2:
+//! * `defaults`: config vars shared by every app, overridden by the settings of each app.
+//! * `schema`: constraints on the values of the config vars, and their default values.
+//!
+//! Profiles are not part of the version 2, they are left for a later version.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let upgraded = migration::migrate("version: \"1\" # comment\napps: []\n", "2");
+//! assert_eq!(upgraded.unwrap(), "version: \"2\" # comment\napps: []\n");
+//! ```
+use yaml::{self, Position};
+
+/// Upgrade of the content of a config file to the next version
+type Migration = fn(&str) -> Result<String, String>;
+
+/// Migrations from a version to the next one, in order
+static MIGRATIONS: &[(&str, &str, Migration)] = &[("1", "2", migrate_v1_to_v2)];
+
+/// Upgrade the content of a config file to a version
+///
+/// # Arguments
+/// * `yaml` - The content of a config file in YAML format
+/// * `target` - The version to upgrade to
+///
+/// # Result
+/// * `Result<String, String>` - The upgraded content or an Error message
+///
+pub fn migrate(yaml: &str, target: &str) -> Result<String, String> {
+    let mut content = yaml.to_string();
+    let mut version = read_version(&content)?.0;
+    while version != target {
+        match MIGRATIONS.iter().find(|migration| migration.0 == version) {
+            Some(&(_, next, upgrade)) => {
+                content = upgrade(&content)?;
+                version = next.to_string();
+            }
+            None => return Err(format!("No migration from version \"{}\"", version)),
+        }
+    }
+    Ok(content)
+}
+
+/// Read the version of a config file, and the position of its value
+///
+/// # Arguments
+/// * `yaml` - The content of a config file in YAML format
+///
+/// # Result
+/// * `Result<(String, Position), String>` - The version and its position, or an Error message
+///
+pub fn read_version(yaml: &str) -> Result<(String, Position), String> {
+    let root = match yaml::parse(yaml) {
+        Ok(Some(root)) => root,
+        Ok(None) => return Err("The config file is empty".to_string()),
+        Err(err) => return Err(format!("{}: {}", err.position, err.message)),
+    };
+    match root.get("version") {
+        Some(node) => match node.as_str() {
+            Some(version) => Ok((version.to_string(), node.position())),
+            None => Err(format!("{}: the version must be a value", node.position())),
+        },
+        None => Err("missing field `version`".to_string()),
+    }
+}
+
+/// Version 2 only adds optional fields, the version number is the only change
+fn migrate_v1_to_v2(yaml: &str) -> Result<String, String> {
+    set_version(yaml, "2")
+}
+
+/// Replace the value of the version of a config file, keeping the rest of the file untouched
+fn set_version(yaml: &str, version: &str) -> Result<String, String> {
+    let position = read_version(yaml)?.1;
+    let mut lines: Vec<String> = yaml.split('\n').map(|line| line.to_string()).collect();
+    let line = match lines.get_mut(position.line - 1) {
+        Some(line) => line,
+        None => return Err(format!("{}: version not found", position)),
+    };
+
+    // Columns count characters, not bytes
+    let start: usize = line
+        .char_indices()
+        .nth(position.column - 1)
+        .map_or(line.len(), |(index, _)| index);
+    let rest = &line[start..];
+    let length = match rest.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => match rest[1..].find(quote) {
+            Some(end) => end + 2,
+            None => return Err(format!("{}: unterminated version", position)),
+        },
+        _ => rest
+            .find(|c: char| c.is_whitespace() || c == '#')
+            .unwrap_or(rest.len()),
+    };
+    let replaced = format!(
+        "{}\"{}\"{}",
+        &line[..start],
+        version,
+        &line[start + length..]
+    );
+    *line = replaced;
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_migrate_v1_to_v2() {
+        let yaml = "# My apps\nversion: \"1\"\napps:\n  - name: \"my-app\" # production\n";
+        let upgraded = migrate(yaml, "2").unwrap();
+        assert_eq!(
+            upgraded,
+            "# My apps\nversion: \"2\"\napps:\n  - name: \"my-app\" # production\n"
+        );
+    }
+
+    #[test]
+    fn should_migrate_unquoted_versions() {
+        assert_eq!(
+            migrate("version: 1 # old\napps: []", "2").unwrap(),
+            "version: \"2\" # old\napps: []"
+        );
+        assert_eq!(
+            migrate("apps: []\nversion: '1'\n", "2").unwrap(),
+            "apps: []\nversion: \"2\"\n"
+        );
+    }
+
+    #[test]
+    fn should_not_change_latest_version() {
+        let yaml = "version: '2'\napps: []\n";
+        assert_eq!(migrate(yaml, "2").unwrap(), yaml);
+    }
+
+    #[test]
+    fn should_fail_on_unknown_version() {
+        let result = migrate("version: \"7\"\napps: []\n", "2");
+        assert_eq!(
+            result.err(),
+            Some("No migration from version \"7\"".to_string())
+        );
+    }
+}
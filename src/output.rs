@@ -0,0 +1,816 @@
+//! Output of the commands, for humans or as a single JSON or YAML document
+//!
+//! In the human format, every result is printed as soon as it is known. In the JSON and YAML
+//! formats, the results are collected and printed as one document when the command ends,
+//! progress messages go to the standard error output.
+//!
+//! # Example
+//!
+//! ```rust
+//! let mut output = Output::new(Format::Json, "validate");
+//! output.message("config.yml is valid".to_string());
+//! assert!(output.finish());
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{self, Value};
+use serde_yaml;
+
+use drift::{KeyState, State};
+use heroku::PlatformError;
+use plan::Change;
+use schema::Violation;
+
+/// Separator printed after the results of an app in the human format
+pub static SEPARATOR: &str = "-------------------------";
+
+/// Formats of the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Human,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Names of the formats, as given on the command line
+    pub const NAMES: &'static [&'static str] = &["human", "json", "yaml"];
+
+    /// Read a format from its name, the human format by default
+    ///
+    /// # Arguments
+    /// * `name` - The name of the format
+    ///
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "json" => Format::Json,
+            "yaml" => Format::Yaml,
+            _ => Format::Human,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Error {
+    /// Id of the error of the Platform API, if it comes from it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Description of the error
+    pub message: String,
+}
+
+/// The document printed at the end of a command, in the JSON and YAML formats
+#[derive(Debug, Serialize)]
+struct Document {
+    command: String,
+    succeeded: bool,
+    results: Vec<Value>,
+    messages: Vec<String>,
+    errors: Vec<Error>,
+}
+
+pub struct Output {
+    /// The format of the output
+    format: Format,
+    /// Print the errors on the standard error output in the human format
+    errors_on_stderr: bool,
+    /// Everything reported by the command
+    document: Document,
+}
+
+impl Output {
+    /// Initialize the output of a command
+    ///
+    /// # Arguments
+    /// * `format` - The format of the output
+    /// * `command` - The name of the command
+    ///
+    pub fn new(format: Format, command: &str) -> Self {
+        Output {
+            format,
+            errors_on_stderr: false,
+            document: Document {
+                command: command.to_string(),
+                succeeded: true,
+                results: Vec::new(),
+                messages: Vec::new(),
+                errors: Vec::new(),
+            },
+        }
+    }
+
+    /// Print the errors on the standard error output in the human format, for commands whose
+    /// output is meant to be used by scripts
+    pub fn print_errors_on_stderr(&mut self) {
+        self.errors_on_stderr = true;
+    }
+
+    /// Report a result of the command, such as the config vars of an app
+    ///
+    /// # Arguments
+    /// * `result` - The result, printed with its `Display` implementation for humans
+    ///
+    pub fn result<T: Serialize + fmt::Display>(&mut self, result: &T) {
+        match self.format {
+            Format::Human => print!("{}", result),
+            _ => match serde_json::to_value(result) {
+                Ok(value) => self.document.results.push(value),
+                Err(err) => self.error(err.to_string()),
+            },
+        }
+    }
+
+    /// Report an information message, such as the path of a saved file
+    pub fn message(&mut self, message: String) {
+        match self.format {
+            Format::Human => println!("{}", message),
+            _ => self.document.messages.push(message),
+        }
+    }
+
+    /// Report the progress of a command, never part of the document
+    pub fn progress(&self, message: &str) {
+        match self.format {
+            Format::Human => println!("{}", message),
+            _ => eprintln!("{}", message),
+        }
+    }
+
+    /// Report an error, the command fails
+    pub fn error(&mut self, message: String) {
+        match self.format {
+            Format::Human if self.errors_on_stderr => eprintln!("Error: {}", message),
+            Format::Human => println!("Error: {}", message),
+            _ => {}
+        }
+        self.document.errors.push(Error { id: None, message });
+        self.fail();
+    }
+
+    /// Report an error of the Platform API, the command fails
+    pub fn platform_error(&mut self, platform_error: &PlatformError) {
+        let message = format!(
+            "PlatformError: {}, {}",
+            platform_error.id, platform_error.message
+        );
+        match self.format {
+            Format::Human if self.errors_on_stderr => eprintln!("{}", message),
+            Format::Human => println!("{}", message),
+            _ => {}
+        }
+        self.document.errors.push(Error {
+            id: Some(platform_error.id.to_string()),
+            message: platform_error.message.to_string(),
+        });
+        self.fail();
+    }
+
+    /// Mark the command as failed, without any message
+    pub fn fail(&mut self) {
+        self.document.succeeded = false;
+    }
+
+    /// Print the document in the JSON and YAML formats
+    ///
+    /// # Result
+    /// * `bool` - true if the command succeeded
+    ///
+    pub fn finish(self) -> bool {
+        let printed = match self.format {
+            Format::Human => Ok(String::new()),
+            Format::Json => serde_json::to_string_pretty(&self.document).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::to_string(&self.document).map_err(|e| e.to_string()),
+        };
+        match printed {
+            Ok(ref document) if document.is_empty() => {}
+            Ok(document) => println!("{}", document.trim_end()),
+            Err(err) => eprintln!("Error: {}", err),
+        }
+        self.document.succeeded
+    }
+}
+
+/// Config vars of an app pulled from heroku
+#[derive(Debug, Serialize)]
+pub struct PulledApp {
+    /// The name of the app
+    pub name: String,
+    /// The config vars of the app
+    pub config_vars: BTreeMap<String, String>,
+    /// The name of the add-on managing a config var, by key
+    pub addons: BTreeMap<String, String>,
+}
+
+impl fmt::Display for PulledApp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for (key, value) in &self.config_vars {
+            match self.addons.get(key) {
+                Some(addon) => writeln!(f, "{}={} (managed by add-on {})", key, value, addon)?,
+                None => writeln!(f, "{}={}", key, value)?,
+            }
+        }
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Values of config vars of an app, printed alone for scripts
+#[derive(Debug, Serialize)]
+pub struct ConfigVarValues {
+    /// The name of the app
+    pub name: String,
+    /// The config vars, by key
+    pub config_vars: BTreeMap<String, String>,
+    /// The keys of the config vars, in the order they were asked for
+    #[serde(skip)]
+    pub keys: Vec<String>,
+}
+
+impl fmt::Display for ConfigVarValues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for key in &self.keys {
+            if let Some(value) = self.config_vars.get(key) {
+                writeln!(f, "{}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes a push makes to the config vars of an app
+#[derive(Debug, Serialize)]
+pub struct PlannedApp {
+    /// The name of the app
+    pub name: String,
+    /// The app requires typing its name to confirm a push
+    pub protected: bool,
+    /// The config vars added, changed or removed
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for PlannedApp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let protection = if self.protected { " (protected)" } else { "" };
+        writeln!(f, "{}{}", self.name, protection)?;
+        if self.changes.is_empty() {
+            writeln!(f, "No changes")?;
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Config vars of an app after an update, with the release it created
+#[derive(Debug, Serialize)]
+pub struct UpdatedApp {
+    /// The name of the app
+    pub name: String,
+    /// The config vars of the app after the update
+    pub config_vars: BTreeMap<String, String>,
+    /// The release created by the update, if it could be fetched
+    pub release: Option<ReleaseInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseInfo {
+    /// Number of releases created since the update started, unknown if the release before the
+    /// update could not be fetched
+    pub count: Option<u64>,
+    /// Version of the latest release
+    pub version: u64,
+    /// Status of the latest release
+    pub status: String,
+    /// Description of the latest release
+    pub description: String,
+}
+
+impl fmt::Display for UpdatedApp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for (key, value) in &self.config_vars {
+            writeln!(f, "{}={}", key, value)?;
+        }
+        if let Some(ref release) = self.release {
+            if let Some(count) = release.count {
+                write!(
+                    f,
+                    "{} release{} created, ",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                )?;
+            }
+            writeln!(
+                f,
+                "v{} ({}): {}",
+                release.version, release.status, release.description
+            )?;
+        }
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Changes of config vars in the releases of an app
+#[derive(Debug, Serialize)]
+pub struct AppHistory {
+    /// The name of the app
+    pub name: String,
+    /// The changes, from the latest release
+    pub changes: Vec<HistoryChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryChange {
+    /// Version of the release
+    pub version: u64,
+    /// Date of creation of the release
+    pub created_at: String,
+    /// Email of the user who created the release
+    pub user: String,
+    /// What the release did to the config vars (ex: "Set", "Remove")
+    pub action: String,
+    /// The keys of the config vars
+    pub keys: Vec<String>,
+}
+
+impl fmt::Display for AppHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for change in &self.changes {
+            writeln!(
+                f,
+                "v{}\t{}\t{}\t{} {}",
+                change.version,
+                change.created_at,
+                change.user,
+                change.action,
+                change.keys.join(", ")
+            )?;
+        }
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Problems of a config file
+#[derive(Debug, Serialize)]
+pub struct Validation {
+    /// The path of the config file
+    pub file: String,
+    /// Every problem found, with its position in the file
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.problems.is_empty() {
+            return writeln!(f, "{} is valid", self.file);
+        }
+        for problem in &self.problems {
+            writeln!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Config vars of an app that do not match the schema of a config file
+#[derive(Debug, Serialize)]
+pub struct CheckedApp {
+    /// The name of the app
+    pub name: String,
+    /// Problems of the settings of the config file
+    pub local: Vec<Violation>,
+    /// Problems of the config vars on heroku
+    pub remote: Vec<Violation>,
+}
+
+impl CheckedApp {
+    /// Check if the app matches the schema
+    pub fn is_valid(&self) -> bool {
+        self.local.is_empty() && self.remote.is_empty()
+    }
+}
+
+impl fmt::Display for CheckedApp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        if self.is_valid() {
+            writeln!(f, "OK")?;
+        }
+        for violation in &self.local {
+            writeln!(f, "local: {}", violation)?;
+        }
+        for violation in &self.remote {
+            writeln!(f, "remote: {}", violation)?;
+        }
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Config vars of several apps, side by side
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    /// The names of the apps, in the order of the values
+    pub apps: Vec<String>,
+    /// The config vars compared, sorted by key
+    pub config_vars: Vec<ComparedConfigVar>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComparedConfigVar {
+    /// The key of the config var
+    pub key: String,
+    /// The value of the config var for every app, None if it is not set
+    pub values: Vec<Option<String>>,
+    /// The config var is missing or has a different value in one of the apps
+    pub differs: bool,
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows: Vec<Vec<&str>> = vec![vec!["KEY"]];
+        rows[0].extend(self.apps.iter().map(|app| app.as_str()));
+        for config_var in &self.config_vars {
+            let mut row = vec![config_var.key.as_str()];
+            row.extend(config_var.values.iter().map(|value| match *value {
+                Some(ref value) => value.as_str(),
+                None => "-",
+            }));
+            rows.push(row);
+        }
+        let widths: Vec<usize> = (0..=self.apps.len())
+            .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+            .collect();
+
+        let differs = self.config_vars.iter().filter(|c| c.differs).count();
+        for (index, row) in rows.iter().enumerate() {
+            // Config vars with differences are marked with a star
+            let marker = match index {
+                0 => " ",
+                _ if self.config_vars[index - 1].differs => "*",
+                _ => " ",
+            };
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect();
+            writeln!(f, "{} {}", marker, cells.join("  ").trim_end())?;
+        }
+        writeln!(
+            f,
+            "{} of {} config vars differ",
+            differs,
+            self.config_vars.len()
+        )
+    }
+}
+
+/// Config vars imported into an app of a config file
+#[derive(Debug, Serialize)]
+pub struct ImportedApp {
+    /// The name of the app
+    pub name: String,
+    /// The path of the imported file
+    pub file: String,
+    /// Config vars not set in the app before
+    pub added: Vec<String>,
+    /// Config vars replaced in the app
+    pub updated: Vec<String>,
+    /// Config vars with another value in the app, kept as they were
+    pub conflicts: Vec<String>,
+}
+
+impl fmt::Display for ImportedApp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for key in &self.added {
+            writeln!(f, "added: {}", key)?;
+        }
+        for key in &self.updated {
+            writeln!(f, "updated: {}", key)?;
+        }
+        for key in &self.conflicts {
+            writeln!(
+                f,
+                "conflict: {} has another value in the config file, use --overwrite to replace it",
+                key
+            )?;
+        }
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Config vars of an app changed on heroku outside of its config file
+#[derive(Debug, Serialize)]
+pub struct AppDrift {
+    /// The name of the app
+    pub name: String,
+    /// The state of every config var, sorted by key
+    pub keys: Vec<KeyState>,
+}
+
+impl AppDrift {
+    /// Check if one of the config vars has drifted, remote-only config vars are only reported
+    pub fn has_drifted(&self) -> bool {
+        self.keys.iter().any(|key| key.state.is_drift())
+    }
+}
+
+impl fmt::Display for AppDrift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for key in self.keys.iter().filter(|key| key.state != State::InSync) {
+            writeln!(f, "{}: {}", key.state, key.key)?;
+        }
+        let count = |state: State| self.keys.iter().filter(|key| key.state == state).count();
+        writeln!(
+            f,
+            "{} in sync, {} drifted, {} remote-only, {} local-only",
+            count(State::InSync),
+            count(State::Drifted),
+            count(State::RemoteOnly),
+            count(State::LocalOnly)
+        )?;
+        writeln!(f, "{}", SEPARATOR)
+    }
+}
+
+/// Render the drift of apps as a JUnit XML report, for CI servers
+///
+/// Every app is a test suite, and every config var a test case failing if it has drifted.
+/// Remote-only config vars are skipped test cases, and the apps whose config vars could not be
+/// read are test suites with an error.
+///
+/// # Arguments
+/// * `apps` - The drift of every app
+/// * `errors` - The name of every app whose config vars could not be read, with the error
+///
+/// # Result
+/// * `String` - The report in JUnit XML format
+///
+pub fn junit_report(apps: &[AppDrift], errors: &[(String, String)]) -> String {
+    let count = |app: &AppDrift, drift: bool| {
+        app.keys
+            .iter()
+            .filter(|k| k.state != State::InSync && k.state.is_drift() == drift)
+            .count()
+    };
+    let failures = |app: &AppDrift| count(app, true);
+    let skipped = |app: &AppDrift| count(app, false);
+    let tests: usize = apps.iter().map(|app| app.keys.len()).sum::<usize>() + errors.len();
+    let total_failures: usize = apps.iter().map(failures).sum();
+    let total_skipped: usize = apps.iter().map(skipped).sum();
+
+    let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    report.push_str(&format!(
+        "<testsuites name=\"hke drift\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">\n",
+        tests,
+        total_failures,
+        errors.len(),
+        total_skipped
+    ));
+    for app in apps {
+        report.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\">\n",
+            escape(&app.name),
+            app.keys.len(),
+            failures(app),
+            skipped(app)
+        ));
+        for key in &app.keys {
+            let test_case = format!(
+                "<testcase classname=\"{}\" name=\"{}\"",
+                escape(&app.name),
+                escape(&key.key)
+            );
+            let message = escape(&format!("config var {} is {}", key.key, key.state));
+            if key.state == State::InSync {
+                report.push_str(&format!("    {}/>\n", test_case));
+            } else if key.state.is_drift() {
+                report.push_str(&format!(
+                    "    {}>\n      <failure message=\"{}\" type=\"{}\"/>\n    </testcase>\n",
+                    test_case, message, key.state
+                ));
+            } else {
+                report.push_str(&format!(
+                    "    {}>\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                    test_case, message
+                ));
+            }
+        }
+        report.push_str("  </testsuite>\n");
+    }
+    for (name, error) in errors {
+        report.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"1\" failures=\"0\" errors=\"1\" skipped=\"0\">\n",
+            escape(name)
+        ));
+        report.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"config vars\">\n      <error message=\"{}\" type=\"platform-error\"/>\n    </testcase>\n",
+            escape(name),
+            escape(error)
+        ));
+        report.push_str("  </testsuite>\n");
+    }
+    report.push_str("</testsuites>\n");
+    report
+}
+
+/// Escape a string for an XML attribute
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_print_apps_for_humans() {
+        let mut config_vars: BTreeMap<String, String> = BTreeMap::new();
+        config_vars.insert("DATABASE_URL".to_string(), "postgres://db".to_string());
+        config_vars.insert("ENV".to_string(), "test".to_string());
+        let mut addons: BTreeMap<String, String> = BTreeMap::new();
+        addons.insert(
+            "DATABASE_URL".to_string(),
+            "postgresql-curly-1234".to_string(),
+        );
+        let app = PulledApp {
+            name: "my-app".to_string(),
+            config_vars,
+            addons,
+        };
+        assert_eq!(
+            app.to_string(),
+            "my-app\nDATABASE_URL=postgres://db (managed by add-on postgresql-curly-1234)\nENV=test\n-------------------------\n"
+        );
+    }
+
+    #[test]
+    fn should_print_values_in_order() {
+        let mut config_vars: BTreeMap<String, String> = BTreeMap::new();
+        config_vars.insert("A_VAR".to_string(), "a".to_string());
+        config_vars.insert("B_VAR".to_string(), "b".to_string());
+        let values = ConfigVarValues {
+            name: "my-app".to_string(),
+            config_vars,
+            keys: vec!["B_VAR".to_string(), "A_VAR".to_string()],
+        };
+        assert_eq!(values.to_string(), "b\na\n");
+    }
+
+    #[test]
+    fn should_print_the_release_of_an_update() {
+        let mut app = UpdatedApp {
+            name: "my-app".to_string(),
+            config_vars: BTreeMap::new(),
+            release: Some(ReleaseInfo {
+                count: Some(2),
+                version: 57,
+                status: "succeeded".to_string(),
+                description: "Set MY_VAR config vars".to_string(),
+            }),
+        };
+        assert_eq!(
+            app.to_string(),
+            "my-app\n2 releases created, v57 (succeeded): Set MY_VAR config vars\n-------------------------\n"
+        );
+
+        app.release.as_mut().unwrap().count = None;
+        assert_eq!(
+            app.to_string(),
+            "my-app\nv57 (succeeded): Set MY_VAR config vars\n-------------------------\n"
+        );
+    }
+
+    #[test]
+    fn should_print_a_comparison_matrix() {
+        let comparison = Comparison {
+            apps: vec!["my-staging-app".to_string(), "my-app".to_string()],
+            config_vars: vec![
+                ComparedConfigVar {
+                    key: "ENV".to_string(),
+                    values: vec![Some("staging".to_string()), Some("production".to_string())],
+                    differs: true,
+                },
+                ComparedConfigVar {
+                    key: "PORT".to_string(),
+                    values: vec![Some("5000".to_string()), Some("5000".to_string())],
+                    differs: false,
+                },
+                ComparedConfigVar {
+                    key: "SENTRY_DSN".to_string(),
+                    values: vec![None, Some("<1>".to_string())],
+                    differs: true,
+                },
+            ],
+        };
+        assert_eq!(
+            comparison.to_string(),
+            concat!(
+                "  KEY         my-staging-app  my-app\n",
+                "* ENV         staging         production\n",
+                "  PORT        5000            5000\n",
+                "* SENTRY_DSN  -               <1>\n",
+                "2 of 3 config vars differ\n"
+            )
+        );
+    }
+
+    #[test]
+    fn should_collect_a_document() {
+        let mut output = Output::new(Format::Json, "history");
+        output.result(&AppHistory {
+            name: "my-app".to_string(),
+            changes: vec![HistoryChange {
+                version: 42,
+                created_at: "2019-09-02T10:12:31Z".to_string(),
+                user: "jane@example.com".to_string(),
+                action: "Set".to_string(),
+                keys: vec!["MY_VAR".to_string()],
+            }],
+        });
+        output.platform_error(&PlatformError {
+            id: "not_found".to_string(),
+            message: "Couldn't find that app.".to_string(),
+        });
+
+        let document = serde_json::to_value(&output.document).unwrap();
+        assert_eq!(document["command"], "history");
+        assert_eq!(document["succeeded"], false);
+        assert_eq!(document["results"][0]["changes"][0]["version"], 42);
+        assert_eq!(document["errors"][0]["id"], "not_found");
+        assert!(!output.finish());
+    }
+
+    #[test]
+    fn should_read_formats() {
+        assert_eq!(Format::from_name("json"), Format::Json);
+        assert_eq!(Format::from_name("yaml"), Format::Yaml);
+        assert_eq!(Format::from_name("human"), Format::Human);
+    }
+
+    fn state(key: &str, state: State) -> KeyState {
+        KeyState {
+            key: key.to_string(),
+            state,
+        }
+    }
+
+    #[test]
+    fn should_only_report_remote_only_config_vars() {
+        let app = AppDrift {
+            name: "my-app".to_string(),
+            keys: vec![
+                state("DATABASE_URL", State::RemoteOnly),
+                state("PORT", State::InSync),
+            ],
+        };
+        assert!(!app.has_drifted());
+    }
+
+    #[test]
+    fn should_render_a_junit_report() {
+        let apps = vec![AppDrift {
+            name: "my-app".to_string(),
+            keys: vec![
+                state("DATABASE_URL", State::RemoteOnly),
+                state("ENV", State::Drifted),
+                state("PORT", State::InSync),
+            ],
+        }];
+        let errors = vec![(
+            "my-app-2".to_string(),
+            "Couldn't find that app.".to_string(),
+        )];
+        assert_eq!(
+            junit_report(&apps, &errors),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<testsuites name=\"hke drift\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\">\n",
+                "  <testsuite name=\"my-app\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\">\n",
+                "    <testcase classname=\"my-app\" name=\"DATABASE_URL\">\n",
+                "      <skipped message=\"config var DATABASE_URL is remote-only\"/>\n",
+                "    </testcase>\n",
+                "    <testcase classname=\"my-app\" name=\"ENV\">\n",
+                "      <failure message=\"config var ENV is drifted\" type=\"drifted\"/>\n",
+                "    </testcase>\n",
+                "    <testcase classname=\"my-app\" name=\"PORT\"/>\n",
+                "  </testsuite>\n",
+                "  <testsuite name=\"my-app-2\" tests=\"1\" failures=\"0\" errors=\"1\" skipped=\"0\">\n",
+                "    <testcase classname=\"my-app-2\" name=\"config vars\">\n",
+                "      <error message=\"Couldn't find that app.\" type=\"platform-error\"/>\n",
+                "    </testcase>\n",
+                "  </testsuite>\n",
+                "</testsuites>\n"
+            )
+        );
+    }
+}
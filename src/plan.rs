@@ -0,0 +1,289 @@
+//! Compute the changes a push makes to the config vars of an app, and save them in a plan
+//!
+//! A plan records the changes of every app with a hash of the config vars observed on heroku,
+//! so that it is only applied if nobody changed them in the meantime.
+//!
+//! # Example
+//!
+//! ```rust
+//! let changes = plan::changes(&remote_config_vars, &app);
+//! for change in &changes {
+//!     println!("{}", change);
+//! }
+//!
+//! let mut plan = Plan::new();
+//! plan.apps.push(AppPlan::new(&remote_config_vars, &app));
+//! plan.save("plan.json", false)?;
+//! ```
+use config::{App, Config};
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+/// Version of the format of the plan files
+pub static VERSION: &str = "2";
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// The config var is not set yet
+    Add,
+    /// The config var is set with another value
+    Change,
+    /// The config var is removed
+    Remove,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Change {
+    /// The key of the config var
+    pub key: String,
+    /// What the push does to the config var
+    pub action: Action,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = match self.action {
+            Action::Add => "+",
+            Action::Change => "~",
+            Action::Remove => "-",
+        };
+        write!(f, "{} {}", sign, self.key)
+    }
+}
+
+/// Compare the config vars of an app on heroku with the ones pushed
+///
+/// # Arguments
+/// * `remote` - The config vars of the app on heroku
+/// * `app` - The settings and removals of the app to push
+///
+/// # Result
+/// * `Vec<Change>` - The config vars added, changed or removed by the push, sorted by key
+///
+pub fn changes(remote: &BTreeMap<String, String>, app: &App) -> Vec<Change> {
+    diff(remote, &app.changes())
+}
+
+/// Compare the config vars of an app on heroku with new values
+///
+/// # Arguments
+/// * `remote` - The config vars of the app on heroku
+/// * `values` - The new value of each config var, `None` to remove it
+///
+/// # Result
+/// * `Vec<Change>` - The config vars added, changed or removed, sorted by key
+///
+pub fn diff(
+    remote: &BTreeMap<String, String>,
+    values: &BTreeMap<String, Option<String>>,
+) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    for (key, value) in values.iter() {
+        let key = key.to_string();
+        let action = match (remote.get(&key), value) {
+            (None, Some(_)) => Action::Add,
+            (Some(current), Some(value)) if current != value => Action::Change,
+            (Some(_), None) => Action::Remove,
+            _ => continue,
+        };
+        changes.push(Change { key, action });
+    }
+    changes
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    /// Version of the format of the plan file
+    pub version: String,
+    /// The changes of every app
+    pub apps: Vec<AppPlan>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AppPlan {
+    /// The name of the app
+    pub name: String,
+    /// Hash of the config vars of the app on heroku when the plan was made
+    pub remote_hash: String,
+    /// The name of the app must be typed to apply the plan, like to push to it
+    pub protected: bool,
+    /// The new value of each config var changed, `None` to remove it
+    pub changes: BTreeMap<String, Option<String>>,
+}
+
+impl Plan {
+    /// Initialize a plan without any change
+    pub fn new() -> Self {
+        Plan {
+            version: VERSION.to_string(),
+            apps: Vec::new(),
+        }
+    }
+
+    /// Read a plan from a file in JSON format
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the plan file
+    ///
+    /// # Result
+    /// * `Result<Plan, String>` - The plan or an Error message
+    ///
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let content = Config::load(path.to_string()).map_err(|err| format!("{}: {}", path, err))?;
+        let plan: Plan =
+            serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?;
+        if plan.version != VERSION {
+            return Err(format!(
+                "{}: unsupported plan version \"{}\", expected \"{}\"",
+                path, plan.version, VERSION
+            ));
+        }
+        Ok(plan)
+    }
+
+    /// Save the plan into a file in JSON format, only readable by its owner
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the plan file
+    /// * `force` - Overwrite the file if it already exists
+    ///
+    /// # Result
+    /// * `Result<String, std::io::Error>` - A success string or an Error message
+    ///
+    pub fn save(&self, path: &str, force: bool) -> Result<String, io::Error> {
+        Config::check_overwrite(path, force)?;
+        let json = serde_json::to_string_pretty(&self)?;
+        Config::write(path, &json)?;
+        Ok(format!("Successfully saved plan at {}", path))
+    }
+}
+
+impl AppPlan {
+    /// Record the changes a push makes to the config vars of an app
+    ///
+    /// # Arguments
+    /// * `remote` - The config vars of the app on heroku
+    /// * `app` - The settings and removals of the app to push
+    ///
+    pub fn new(remote: &BTreeMap<String, String>, app: &App) -> Self {
+        let changes = changes(remote, app)
+            .into_iter()
+            .map(|change| {
+                let value = match change.action {
+                    Action::Remove => None,
+                    _ => app.settings.get(&change.key).cloned(),
+                };
+                (change.key, value)
+            })
+            .collect();
+        AppPlan {
+            name: app.name.to_string(),
+            remote_hash: remote_hash(remote),
+            protected: app.protected,
+            changes,
+        }
+    }
+}
+
+/// Compute a hash of the config vars of an app, to detect any change
+///
+/// # Arguments
+/// * `remote` - The config vars of the app on heroku
+///
+/// # Result
+/// * `String` - The SHA-256 hash of the config vars, in hexadecimal
+///
+pub fn remote_hash(remote: &BTreeMap<String, String>) -> String {
+    let json = serde_json::to_string(remote).unwrap_or_default();
+    Sha256::digest(json.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn should_list_changes() {
+        let mut remote: BTreeMap<String, String> = BTreeMap::new();
+        remote.insert("ENV".to_string(), "staging".to_string());
+        remote.insert("PORT".to_string(), "5000".to_string());
+        remote.insert("OLD_VAR".to_string(), "1".to_string());
+        let mut app = App::new();
+        app.name = "my-app".to_string();
+        app.settings
+            .insert("ENV".to_string(), "production".to_string());
+        app.settings.insert("PORT".to_string(), "5000".to_string());
+        app.settings.insert("NEW_VAR".to_string(), "1".to_string());
+        app.unset = vec!["OLD_VAR".to_string(), "UNKNOWN_VAR".to_string()];
+
+        let changes = changes(&remote, &app);
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    key: "ENV".to_string(),
+                    action: Action::Change,
+                },
+                Change {
+                    key: "NEW_VAR".to_string(),
+                    action: Action::Add,
+                },
+                Change {
+                    key: "OLD_VAR".to_string(),
+                    action: Action::Remove,
+                },
+            ]
+        );
+        assert_eq!(changes[0].to_string(), "~ ENV");
+
+        let app_plan = AppPlan::new(&remote, &app);
+        assert_eq!(app_plan.name, "my-app");
+        assert_eq!(app_plan.remote_hash, remote_hash(&remote));
+        assert_eq!(app_plan.changes.len(), 3);
+        assert_eq!(app_plan.changes["ENV"], Some("production".to_string()));
+        assert_eq!(app_plan.changes["OLD_VAR"], None);
+        assert!(!app_plan.protected);
+        assert_eq!(diff(&remote, &app_plan.changes), changes);
+    }
+
+    #[test]
+    fn should_hash_remote_config_vars() {
+        let mut remote: BTreeMap<String, String> = BTreeMap::new();
+        remote.insert("ENV".to_string(), "staging".to_string());
+        let hash = remote_hash(&remote);
+        assert_eq!(hash.len(), 64);
+
+        remote.insert("ENV".to_string(), "production".to_string());
+        assert_ne!(remote_hash(&remote), hash);
+    }
+
+    #[test]
+    fn should_save_and_read_plans() {
+        let path = "tests/plan_test.json";
+        let mut plan = Plan::new();
+        let mut changes: BTreeMap<String, Option<String>> = BTreeMap::new();
+        changes.insert("ENV".to_string(), Some("production".to_string()));
+        changes.insert("OLD_VAR".to_string(), None);
+        plan.apps.push(AppPlan {
+            name: "my-app".to_string(),
+            remote_hash: "1234".to_string(),
+            protected: true,
+            changes,
+        });
+        assert_eq!(
+            plan.save(path, true).unwrap(),
+            "Successfully saved plan at tests/plan_test.json"
+        );
+        let saved = Plan::from_path(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(saved.apps, plan.apps);
+    }
+}
@@ -0,0 +1,154 @@
+//! Run a local command with the config vars of an app in its environment
+//!
+//! The config vars are only given to the command through its environment, they are never
+//! written to disk.
+//!
+//! # Example
+//!
+//! ```rust
+//! let vars = run::environment(config_vars, &KeyFilter::all(), &[], BTreeMap::new());
+//! let code = run::exec("cargo", &["run"], &vars)?;
+//! ```
+use keys::KeyFilter;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+
+/// Environment variable of the heroku API token of hke, loaded from the `.env` file
+static API_TOKEN_VAR: &str = "HK_API_TOKEN";
+
+/// Select the config vars to add to the environment of the command
+///
+/// # Arguments
+/// * `config_vars` - The config vars of the app
+/// * `only` - The keys of the config vars to add, every config var is added by default
+/// * `exclude` - The keys of the config vars not to add
+/// * `overrides` - Local config vars, replacing the ones of the app
+///
+/// # Result
+/// * `BTreeMap<String, String>` - The config vars to add to the environment
+///
+pub fn environment(
+    config_vars: BTreeMap<String, String>,
+    only: &KeyFilter,
+    exclude: &[&str],
+    overrides: BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut vars: BTreeMap<String, String> = config_vars
+        .into_iter()
+        .filter(|(key, _)| only.matches(key) && !exclude.contains(&key.as_str()))
+        .collect();
+    vars.extend(overrides);
+    vars
+}
+
+/// Run a command with config vars added to the current environment
+///
+/// The heroku API token of hke is removed from the environment, unless the app sets it as one of
+/// its config vars.
+///
+/// On unix, the command replaces the current process, so that it receives the signals sent to
+/// hke and its exit code is the one of hke. Elsewhere, the command is run as a child process.
+///
+/// # Arguments
+/// * `program` - The program to run
+/// * `args` - The arguments of the program
+/// * `vars` - The config vars to add to the environment of the program
+///
+/// # Result
+/// * `Result<i32, String>` - The exit code of the command, or an Error message if it failed to start
+///
+pub fn exec(program: &str, args: &[&str], vars: &BTreeMap<String, String>) -> Result<i32, String> {
+    start(&mut command(program, args, vars)).map_err(|err| format!("{}: {}", program, err))
+}
+
+/// Command running a program with config vars added to the current environment
+fn command(program: &str, args: &[&str], vars: &BTreeMap<String, String>) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    if !vars.contains_key(API_TOKEN_VAR) {
+        command.env_remove(API_TOKEN_VAR);
+    }
+    command.envs(vars);
+    command
+}
+
+#[cfg(unix)]
+fn start(command: &mut Command) -> Result<i32, ::std::io::Error> {
+    // Only returns if the command could not be started
+    Err(command.exec())
+}
+
+#[cfg(not(unix))]
+fn start(command: &mut Command) -> Result<i32, ::std::io::Error> {
+    let status = command.status()?;
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_add_every_config_var() {
+        let config_vars = vars(&[("ENV", "production"), ("PORT", "5000")]);
+        assert_eq!(
+            environment(config_vars.clone(), &KeyFilter::all(), &[], BTreeMap::new()),
+            config_vars
+        );
+    }
+
+    #[test]
+    fn should_filter_config_vars() {
+        let config_vars = vars(&[("AWS_KEY", "1"), ("AWS_SECRET", "2"), ("PORT", "5000")]);
+        let only = KeyFilter::new(&["AWS_KEY", "AWS_SECRET"], &[]).unwrap();
+        assert_eq!(
+            environment(config_vars, &only, &["AWS_SECRET"], BTreeMap::new()),
+            vars(&[("AWS_KEY", "1")])
+        );
+    }
+
+    #[test]
+    fn should_override_config_vars() {
+        let config_vars = vars(&[("DATABASE_URL", "postgres://prod"), ("PORT", "5000")]);
+        let overrides = vars(&[("DATABASE_URL", "postgres://localhost")]);
+        assert_eq!(
+            environment(config_vars, &KeyFilter::all(), &[], overrides),
+            vars(&[("DATABASE_URL", "postgres://localhost"), ("PORT", "5000")])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_not_leak_the_api_token() {
+        use std::env;
+
+        env::set_var(API_TOKEN_VAR, "hke-secret-token");
+        let output = command("env", &[], &vars(&[("PORT", "5000")]))
+            .output()
+            .unwrap();
+        let child_env = String::from_utf8_lossy(&output.stdout).to_string();
+        assert!(child_env.contains("PORT=5000"));
+        assert!(!child_env.contains("hke-secret-token"));
+
+        let output = command("env", &[], &vars(&[(API_TOKEN_VAR, "app-token")]))
+            .output()
+            .unwrap();
+        let child_env = String::from_utf8_lossy(&output.stdout).to_string();
+        assert!(child_env.contains("HK_API_TOKEN=app-token"));
+    }
+
+    #[test]
+    fn should_fail_on_unknown_programs() {
+        assert!(exec("hke-unknown-program", &[], &BTreeMap::new()).is_err());
+    }
+}
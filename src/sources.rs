@@ -0,0 +1,213 @@
+//! Read the values of settings from outside of the config file
+//!
+//! A setting can be given a source directive instead of a value, so that secrets are not
+//! written in the config file:
+//!
+//! ```yaml
+//! settings:
+//!   STRIPE_KEY: { file: ./secrets/stripe.key }
+//!   GITHUB_TOKEN: { env: LOCAL_GITHUB_TOKEN }
+//!   SENDGRID_KEY: { command: "pass show sendgrid" }
+//!   DATABASE_URL: "vault://secret/data/my-app#DATABASE_URL"
+//! ```
+//!
+//! Values referencing a secret of HashiCorp Vault are read with the `vault` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use heroku_env::sources::Source;
+//! use std::path::Path;
+//!
+//! let source = Source::Env("LOCAL_GITHUB_TOKEN".to_string());
+//! let value = source.resolve(Path::new(".")).unwrap();
+//! ```
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+#[cfg(feature = "vault")]
+use vault;
+
+/// Names of the source directives
+pub static DIRECTIVES: &[&str] = &["file", "env", "command"];
+
+/// Prefix of the values referencing a secret of HashiCorp Vault
+pub static VAULT_PREFIX: &str = "vault://";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Path to a file containing the value, relative to the config file
+    File(String),
+    /// Name of an environment variable of the local machine
+    Env(String),
+    /// Shell command printing the value on its standard output
+    Command(String),
+    /// Key of a secret of HashiCorp Vault, as `PATH#KEY`, written as a `vault://` value
+    #[serde(skip)]
+    Vault(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::File(ref path) => write!(f, "file {}", path),
+            Source::Env(ref name) => write!(f, "env {}", name),
+            Source::Command(ref command) => write!(f, "command `{}`", command),
+            Source::Vault(ref reference) => write!(f, "{}{}", VAULT_PREFIX, reference),
+        }
+    }
+}
+
+impl Source {
+    /// Read the source referenced by the value of a setting, if any
+    ///
+    /// # Arguments
+    /// * `value` - The value of a setting in the config file
+    ///
+    pub fn from_value(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(2, "://");
+        match (parts.next(), parts.next()) {
+            (Some("vault"), Some(reference)) => Some(Source::Vault(reference.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Read the value of a setting from its source
+    ///
+    /// The trailing new line of files and command outputs is not part of the value.
+    ///
+    /// # Arguments
+    /// * `base` - The directory of the config file, relative paths of files start from it
+    ///
+    /// # Result
+    /// * `Result<String, String>` - The value or an Error message
+    ///
+    pub fn resolve(&self, base: &Path) -> Result<String, String> {
+        match *self {
+            Source::File(ref path) => fs::read_to_string(base.join(path))
+                .map(|content| trim_new_line(&content))
+                .map_err(|err| format!("{}: {}", path, err)),
+            Source::Env(ref name) => {
+                env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+            }
+            Source::Command(ref command) => {
+                let output = shell(command)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .map_err(|err| format!("{}: {}", self, err))?;
+                if !output.status.success() {
+                    return Err(match output.status.code() {
+                        Some(code) => format!("{} failed with exit code {}", self, code),
+                        None => format!("{} was terminated by a signal", self),
+                    });
+                }
+                String::from_utf8(output.stdout)
+                    .map(|stdout| trim_new_line(&stdout))
+                    .map_err(|_| format!("{} did not print valid UTF-8", self))
+            }
+            Source::Vault(ref reference) => read_vault(reference),
+        }
+    }
+}
+
+#[cfg(feature = "vault")]
+fn read_vault(reference: &str) -> Result<String, String> {
+    vault::read(reference)
+}
+
+#[cfg(not(feature = "vault"))]
+fn read_vault(_reference: &str) -> Result<String, String> {
+    Err("reading secrets from Vault requires hke to be built with the `vault` feature".to_string())
+}
+
+/// Command running a command line in the shell of the platform
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// Remove the last new line of a content, the ones before are part of the value
+fn trim_new_line(content: &str) -> String {
+    let mut value = content.to_string();
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_files() {
+        let source = Source::File("sources_test.key".to_string());
+        assert_eq!(source.resolve(Path::new("tests")).unwrap(), "sk_test_1234");
+
+        let source = Source::File("missing.key".to_string());
+        assert!(source
+            .resolve(Path::new("tests"))
+            .unwrap_err()
+            .starts_with("missing.key: "));
+    }
+
+    #[test]
+    fn should_resolve_env_vars() {
+        env::set_var("HKE_SOURCES_TEST", "secret");
+        let source = Source::Env("HKE_SOURCES_TEST".to_string());
+        assert_eq!(source.resolve(Path::new(".")).unwrap(), "secret");
+
+        let source = Source::Env("HKE_SOURCES_UNSET".to_string());
+        assert_eq!(
+            source.resolve(Path::new(".")),
+            Err("environment variable HKE_SOURCES_UNSET is not set".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_resolve_commands() {
+        let source = Source::Command("printf 'a\\nb\\n'".to_string());
+        assert_eq!(source.resolve(Path::new(".")).unwrap(), "a\nb");
+
+        let source = Source::Command("exit 3".to_string());
+        assert_eq!(
+            source.resolve(Path::new(".")),
+            Err("command `exit 3` failed with exit code 3".to_string())
+        );
+    }
+
+    #[test]
+    fn should_read_vault_references() {
+        let source = Source::from_value("vault://secret/data/my-app#STRIPE_KEY").unwrap();
+        assert_eq!(
+            source,
+            Source::Vault("secret/data/my-app#STRIPE_KEY".to_string())
+        );
+        assert_eq!(source.to_string(), "vault://secret/data/my-app#STRIPE_KEY");
+        assert_eq!(Source::from_value("https://vault.example.com"), None);
+    }
+
+    #[test]
+    fn should_trim_the_last_new_line() {
+        assert_eq!(trim_new_line("value\r\n"), "value");
+        assert_eq!(trim_new_line("value\n\n"), "value\n");
+        assert_eq!(trim_new_line("value"), "value");
+    }
+}
@@ -0,0 +1,537 @@
+//! Validation of a config file, reporting every problem with its position in the file
+//!
+//! Checks the version of the file, the unknown fields, the rules of the schema, the names of the
+//! apps (valid Heroku app names, without duplicates), the keys of their config vars and the
+//! source directives of their settings.
+//!
+//! # Example
+//!
+//! ```rust
+//! use heroku_env::validation;
+//!
+//! let problems = validation::validate("version: \"7\"\napps: []\n", &["1"]);
+//! assert_eq!(problems[0].to_string(), "1:10: unsupported version \"7\", expected one of \"1\"");
+//! ```
+use std::fmt;
+
+use regex::Regex;
+
+use sources::DIRECTIVES;
+use yaml::{self, Node, Position};
+
+/// Fields allowed at the root of a config file
+static CONFIG_FIELDS: &[&str] = &["version", "defaults", "schema", "apps"];
+
+/// Fields required at the root of a config file
+static REQUIRED_CONFIG_FIELDS: &[&str] = &["version", "apps"];
+
+/// Fields only allowed from a version of the config file
+static VERSIONED_FIELDS: &[(&str, &str)] = &[("defaults", "2"), ("schema", "2")];
+
+/// Fields allowed in an app of a config file
+static APP_FIELDS: &[&str] = &["name", "settings", "unset", "protected"];
+
+/// Fields allowed in a rule of the schema
+static RULE_FIELDS: &[&str] = &["type", "required", "default", "values", "pattern"];
+
+/// Types of config vars of the schema
+static RULE_TYPES: &[&str] = &["string", "int", "bool", "url", "enum", "regex"];
+
+#[derive(Debug)]
+pub struct Problem {
+    /// Where the problem was found in the file
+    pub position: Position,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl Problem {
+    fn new(position: Position, message: String) -> Self {
+        Problem { position, message }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+/// Validate the content of a config file
+///
+/// # Arguments
+/// * `yaml` - The content of a config file in YAML format
+/// * `versions` - The versions of config file supported
+///
+/// # Result
+/// * `Vec<Problem>` - Every problem found, in the order of the file
+///
+pub fn validate(yaml: &str, versions: &[&str]) -> Vec<Problem> {
+    let root = match yaml::parse(yaml) {
+        Ok(Some(root)) => root,
+        Ok(None) => {
+            let start = Position { line: 1, column: 1 };
+            return vec![Problem::new(start, "the config file is empty".to_string())];
+        }
+        Err(err) => return vec![Problem::new(err.position, err.message)],
+    };
+
+    let mut problems: Vec<Problem> = Vec::new();
+    let pairs = match root {
+        Node::Mapping(ref pairs, _) => pairs,
+        _ => {
+            let message = format!("expected a map at the root, found {}", root.kind());
+            return vec![Problem::new(root.position(), message)];
+        }
+    };
+
+    check_fields(pairs, CONFIG_FIELDS, &mut problems);
+    check_required(&root, REQUIRED_CONFIG_FIELDS, &mut problems);
+
+    if let Some(node) = root.get("version") {
+        match node.as_str() {
+            Some(v) if versions.contains(&v) => {
+                for &(field, since) in VERSIONED_FIELDS {
+                    if let Some(key) = pairs
+                        .iter()
+                        .map(|p| &p.0)
+                        .find(|k| k.as_str() == Some(field))
+                    {
+                        if version_number(v) < version_number(since) {
+                            problems.push(Problem::new(
+                                key.position(),
+                                format!(
+                                    "field `{}` requires version \"{}\", the file is in version \"{}\"",
+                                    field, since, v
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+            Some(v) => problems.push(Problem::new(
+                node.position(),
+                format!(
+                    "unsupported version \"{}\", expected one of \"{}\"",
+                    v,
+                    versions.join("\", \"")
+                ),
+            )),
+            None => problems.push(expected("a value", node)),
+        }
+    }
+
+    match root.get("defaults") {
+        Some(Node::Mapping(defaults, _)) => check_settings(defaults, false, &mut problems),
+        Some(node) if !node.is_null() => problems.push(expected("a map", node)),
+        _ => {}
+    }
+
+    match root.get("schema") {
+        Some(Node::Mapping(rules, _)) => check_schema(rules, &mut problems),
+        Some(node) if !node.is_null() => problems.push(expected("a map", node)),
+        _ => {}
+    }
+
+    match root.get("apps") {
+        Some(Node::Sequence(apps, _)) => check_apps(apps, &mut problems),
+        Some(node) => problems.push(expected("a list", node)),
+        None => {}
+    }
+
+    problems.sort_by_key(|problem| (problem.position.line, problem.position.column));
+    problems
+}
+
+/// Check the apps of a config file
+fn check_apps(apps: &[Node], problems: &mut Vec<Problem>) {
+    let mut names: Vec<(&str, Position)> = Vec::new();
+    for app in apps {
+        let pairs = match *app {
+            Node::Mapping(ref pairs, _) => pairs,
+            _ => {
+                problems.push(expected("a map", app));
+                continue;
+            }
+        };
+        check_fields(pairs, APP_FIELDS, problems);
+        check_required(app, &["name"], problems);
+
+        if let Some(node) = app.get("name") {
+            match node.as_str() {
+                Some(name) => {
+                    if !is_app_name(name) {
+                        problems.push(Problem::new(
+                            node.position(),
+                            format!(
+                                "invalid app name \"{}\", it must start with a letter, end with \
+                                 a letter or a digit, and only contain 3 to 30 lowercase letters, \
+                                 digits and dashes",
+                                name
+                            ),
+                        ));
+                    }
+                    match names.iter().find(|n| n.0 == name) {
+                        Some(first) => problems.push(Problem::new(
+                            node.position(),
+                            format!(
+                                "duplicate app name \"{}\", first defined at line {}",
+                                name, first.1.line
+                            ),
+                        )),
+                        None => names.push((name, node.position())),
+                    }
+                }
+                None => problems.push(expected("a value", node)),
+            }
+        }
+
+        match app.get("settings") {
+            Some(Node::Mapping(settings, _)) => check_settings(settings, true, problems),
+            Some(node) if !node.is_null() => problems.push(expected("a map", node)),
+            _ => {}
+        }
+
+        match app.get("unset") {
+            Some(Node::Sequence(keys, _)) => {
+                for key in keys {
+                    check_key(key, problems);
+                }
+            }
+            Some(node) if !node.is_null() => problems.push(expected("a list", node)),
+            _ => {}
+        }
+
+        if let Some(node) = app.get("protected") {
+            check_bool(node, problems);
+        }
+    }
+}
+
+/// Check that a flag is either true or false
+fn check_bool(node: &Node, problems: &mut Vec<Problem>) {
+    match node.as_str() {
+        Some("true") | Some("false") => {}
+        Some(value) => problems.push(Problem::new(
+            node.position(),
+            format!("expected true or false, found \"{}\"", value),
+        )),
+        None => problems.push(expected("true or false", node)),
+    }
+}
+
+/// Check the rules of the config vars of the schema
+fn check_schema(rules: &[(Node, Node)], problems: &mut Vec<Problem>) {
+    for (key, rule) in rules {
+        check_key(key, problems);
+        let pairs = match *rule {
+            Node::Mapping(ref pairs, _) => pairs,
+            _ => {
+                problems.push(expected("a map", rule));
+                continue;
+            }
+        };
+        check_fields(pairs, RULE_FIELDS, problems);
+
+        let kind = match rule.get("type") {
+            Some(node) => match node.as_str() {
+                Some(kind) if RULE_TYPES.contains(&kind) => kind,
+                Some(kind) => {
+                    problems.push(Problem::new(
+                        node.position(),
+                        format!(
+                            "unknown type \"{}\", expected one of \"{}\"",
+                            kind,
+                            RULE_TYPES.join("\", \"")
+                        ),
+                    ));
+                    ""
+                }
+                None => {
+                    problems.push(expected("a value", node));
+                    ""
+                }
+            },
+            None => "string",
+        };
+        if kind == "enum" {
+            check_required(rule, &["values"], problems);
+        }
+        if kind == "regex" {
+            check_required(rule, &["pattern"], problems);
+        }
+
+        if let Some(node) = rule.get("required") {
+            check_bool(node, problems);
+        }
+        if let Some(node) = rule.get("default") {
+            if node.as_str().is_none() {
+                problems.push(expected("a value", node));
+            }
+        }
+        match rule.get("values") {
+            Some(Node::Sequence(values, _)) => {
+                for value in values.iter().filter(|value| value.as_str().is_none()) {
+                    problems.push(expected("a value", value));
+                }
+            }
+            Some(node) => problems.push(expected("a list", node)),
+            None => {}
+        }
+        if let Some(node) = rule.get("pattern") {
+            match node.as_str() {
+                Some(pattern) => {
+                    if let Err(err) = Regex::new(pattern) {
+                        problems.push(Problem::new(
+                            node.position(),
+                            format!("invalid pattern /{}/: {}", pattern, err),
+                        ));
+                    }
+                }
+                None => problems.push(expected("a value", node)),
+            }
+        }
+    }
+}
+
+/// Check the keys and values of the settings of an app, or of the defaults
+fn check_settings(settings: &[(Node, Node)], directives: bool, problems: &mut Vec<Problem>) {
+    for (key, value) in settings {
+        check_key(key, problems);
+        match value {
+            Node::Mapping(pairs, _) if directives => check_source(value, pairs, problems),
+            _ if value.as_str().is_none() => problems.push(expected("a value", value)),
+            _ => {}
+        }
+    }
+}
+
+/// Check a source directive, a map with a single directive and its argument
+fn check_source(node: &Node, pairs: &[(Node, Node)], problems: &mut Vec<Problem>) {
+    check_fields(pairs, DIRECTIVES, problems);
+    if pairs.len() != 1 {
+        problems.push(Problem::new(
+            node.position(),
+            format!(
+                "expected a single directive among `{}`",
+                DIRECTIVES.join("`, `")
+            ),
+        ));
+    }
+    for (_, argument) in pairs {
+        if argument.as_str().is_none() || argument.is_null() {
+            problems.push(expected("a value", argument));
+        }
+    }
+}
+
+/// Numeric value of a version, to compare versions
+fn version_number(version: &str) -> u32 {
+    version.parse().unwrap_or(0)
+}
+
+/// Report the fields of a map that are not part of the allowed fields
+fn check_fields(pairs: &[(Node, Node)], fields: &[&str], problems: &mut Vec<Problem>) {
+    for (key, _) in pairs {
+        let name = key.as_str().unwrap_or("");
+        if !fields.contains(&name) {
+            problems.push(Problem::new(
+                key.position(),
+                format!(
+                    "unknown field `{}`, expected one of `{}`",
+                    name,
+                    fields.join("`, `")
+                ),
+            ));
+        }
+    }
+}
+
+/// Report the fields missing from a map
+fn check_required(node: &Node, fields: &[&str], problems: &mut Vec<Problem>) {
+    for field in fields {
+        if node.get(field).is_none() {
+            problems.push(Problem::new(
+                node.position(),
+                format!("missing field `{}`", field),
+            ));
+        }
+    }
+}
+
+/// Report the keys of config vars with an invalid name
+fn check_key(key: &Node, problems: &mut Vec<Problem>) {
+    match key.as_str() {
+        Some(name) if is_config_var_key(name) => {}
+        Some(name) => problems.push(Problem::new(
+            key.position(),
+            format!(
+                "invalid config var key \"{}\", it must start with a letter or an underscore, \
+                 and only contain letters, digits and underscores",
+                name
+            ),
+        )),
+        None => problems.push(expected("a value", key)),
+    }
+}
+
+/// Problem of a node that is not of the expected kind
+fn expected(kind: &str, node: &Node) -> Problem {
+    Problem::new(
+        node.position(),
+        format!("expected {}, found {}", kind, node.kind()),
+    )
+}
+
+/// Check if a name is a valid Heroku app name
+///
+/// # Arguments
+/// * `name` - The name of an app
+///
+pub fn is_app_name(name: &str) -> bool {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    name.len() >= 3
+        && name.len() <= 30
+        && valid_chars
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && !name.ends_with('-')
+}
+
+/// Check if a key is a valid name for a config var
+///
+/// # Arguments
+/// * `key` - The key of a config var
+///
+pub fn is_config_var_key(key: &str) -> bool {
+    let valid_chars = key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    !key.is_empty() && valid_chars && !key.starts_with(|c: char| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(yaml: &str) -> Vec<String> {
+        validate(yaml, &["1", "2"])
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn should_accept_valid_config() {
+        let yaml = "version: \"1\"\napps:\n  - name: \"my-app\"\n    settings:\n      MY_VAR: \"1\"\n    unset:\n      - OLD_VAR\n";
+        assert!(messages(yaml).is_empty());
+    }
+
+    #[test]
+    fn should_report_every_problem() {
+        let yaml = "version: \"7\"\nmode: fast\napps:\n  - name: \"my-app\"\n  - name: \"My_App\"\n    settings:\n      1_VAR: \"1\"\n  - name: \"my-app\"\n    vars: {}\n";
+        assert_eq!(
+            messages(yaml),
+            vec![
+                "1:10: unsupported version \"7\", expected one of \"1\", \"2\"",
+                "2:1: unknown field `mode`, expected one of `version`, `defaults`, `schema`, `apps`",
+                "5:11: invalid app name \"My_App\", it must start with a letter, end with a letter or a digit, and only contain 3 to 30 lowercase letters, digits and dashes",
+                "7:7: invalid config var key \"1_VAR\", it must start with a letter or an underscore, and only contain letters, digits and underscores",
+                "8:11: duplicate app name \"my-app\", first defined at line 4",
+                "9:5: unknown field `vars`, expected one of `name`, `settings`, `unset`, `protected`",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_check_defaults() {
+        let yaml = "version: \"2\"\ndefaults:\n  MY-VAR: \"1\"\napps: []\n";
+        assert_eq!(
+            messages(yaml),
+            vec!["3:3: invalid config var key \"MY-VAR\", it must start with a letter or an underscore, and only contain letters, digits and underscores"]
+        );
+        let yaml = "version: \"1\"\ndefaults:\n  MY_VAR: \"1\"\napps: []\n";
+        assert_eq!(
+            messages(yaml),
+            vec!["2:1: field `defaults` requires version \"2\", the file is in version \"1\""]
+        );
+    }
+
+    #[test]
+    fn should_check_source_directives() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    settings:\n      STRIPE_KEY: { file: ./stripe.key }\n      TOKEN: { env: LOCAL_TOKEN }\n";
+        assert!(messages(yaml).is_empty());
+
+        let yaml = "version: \"2\"\ndefaults:\n  TOKEN: { env: LOCAL_TOKEN }\napps:\n  - name: \"my-app\"\n    settings:\n      A: { vault: secret/a }\n      B: { env: B, file: b }\n      C: { command: [pass, show] }\n";
+        assert_eq!(
+            messages(yaml),
+            vec![
+                "3:12: expected a value, found a map",
+                "7:12: unknown field `vault`, expected one of `file`, `env`, `command`",
+                "8:12: expected a single directive among `file`, `env`, `command`",
+                "9:21: expected a value, found a list",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_check_schema() {
+        let yaml = "version: \"2\"\nschema:\n  PORT:\n    type: int\n    required: true\n  LOG_LEVEL:\n    type: enum\n    values: [debug, info]\n    default: info\napps: []\n";
+        assert!(messages(yaml).is_empty());
+
+        let yaml = "version: \"2\"\nschema:\n  PORT:\n    type: number\n    required: yes\n  LOG_LEVEL:\n    type: enum\n  VERSION:\n    type: regex\n    pattern: \"v[0-9\"\napps: []\n";
+        let problems = messages(yaml);
+        assert_eq!(problems.len(), 4);
+        assert_eq!(
+            problems[0],
+            "4:11: unknown type \"number\", expected one of \"string\", \"int\", \"bool\", \"url\", \"enum\", \"regex\""
+        );
+        assert_eq!(problems[1], "5:15: expected true or false, found \"yes\"");
+        assert_eq!(problems[2], "7:5: missing field `values`");
+        assert!(problems[3].starts_with("10:14: invalid pattern /v[0-9/: "));
+    }
+
+    #[test]
+    fn should_report_missing_fields() {
+        assert_eq!(
+            messages("apps:\n  - settings: {}\n"),
+            vec!["1:1: missing field `version`", "2:5: missing field `name`"]
+        );
+    }
+
+    #[test]
+    fn should_report_syntax_errors() {
+        let problems = messages("version: \"1\"\napps: [\n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("3:1: "));
+    }
+
+    #[test]
+    fn should_check_protected_apps() {
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    protected: true\n";
+        assert!(messages(yaml).is_empty());
+        let yaml = "version: \"2\"\napps:\n  - name: \"my-app\"\n    protected: always\n";
+        assert_eq!(
+            messages(yaml),
+            vec!["4:16: expected true or false, found \"always\""]
+        );
+    }
+
+    #[test]
+    fn should_check_app_names() {
+        assert!(is_app_name("my-fuzzy-app-2"));
+        assert!(!is_app_name("my"));
+        assert!(!is_app_name("2-my-app"));
+        assert!(!is_app_name("my-app-"));
+        assert!(!is_app_name("my_app"));
+        assert!(!is_app_name("a-very-long-app-name-for-heroku"));
+    }
+
+    #[test]
+    fn should_check_config_var_keys() {
+        assert!(is_config_var_key("DATABASE_URL"));
+        assert!(is_config_var_key("_private"));
+        assert!(!is_config_var_key("2FA_SECRET"));
+        assert!(!is_config_var_key("MY-VAR"));
+        assert!(!is_config_var_key(""));
+    }
+}
@@ -0,0 +1,203 @@
+//! Read secrets from the KV secrets engine of HashiCorp Vault
+//!
+//! Settings reference a key of a secret as `vault://PATH#KEY`, the path being the one of the
+//! HTTP API without the `/v1/` prefix (`secret/data/my-app` for the version 2 of the KV engine).
+//! Vault is reached at `VAULT_ADDR` with the token in `VAULT_TOKEN`.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let value = vault::read("secret/data/my-app#STRIPE_KEY")?;
+//! ```
+use reqwest;
+use serde_json::{self, Value};
+use std::env;
+
+/// Read a key of a secret, with the address and the token of the environment
+///
+/// # Arguments
+/// * `reference` - The path of the secret and the key, as `PATH#KEY`
+///
+/// # Result
+/// * `Result<String, String>` - The value or an Error message
+///
+pub fn read(reference: &str) -> Result<String, String> {
+    let address = env::var("VAULT_ADDR").map_err(|_| "VAULT_ADDR is not set".to_string())?;
+    let token = env::var("VAULT_TOKEN").map_err(|_| "VAULT_TOKEN is not set".to_string())?;
+    read_from(&address, &token, reference)
+}
+
+/// Read a key of a secret from a Vault server
+///
+/// # Arguments
+/// * `address` - The address of the server (ex: https://vault.example.com:8200)
+/// * `token` - The Vault token
+/// * `reference` - The path of the secret and the key, as `PATH#KEY`
+///
+/// # Result
+/// * `Result<String, String>` - The value or an Error message
+///
+pub fn read_from(address: &str, token: &str, reference: &str) -> Result<String, String> {
+    let (path, key) = match reference.find('#') {
+        Some(index) if index > 0 && index < reference.len() - 1 => {
+            (&reference[..index], &reference[index + 1..])
+        }
+        _ => {
+            return Err(format!(
+                "invalid reference vault://{}, expected vault://PATH#KEY",
+                reference
+            ))
+        }
+    };
+    let url = format!(
+        "{}/v1/{}",
+        address.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+
+    let mut response = reqwest::Client::new()
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .map_err(|err| format!("{}: {}", url, err))?;
+    let body = response.text().map_err(|err| format!("{}: {}", url, err))?;
+    let document: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+    if !response.status().is_success() {
+        return Err(format!(
+            "{} {}: {}",
+            path,
+            response.status(),
+            error_from_document(&document)
+        ));
+    }
+
+    match secret_data(&document).and_then(|data| data.get(key)) {
+        Some(Value::String(value)) => Ok(value.to_string()),
+        Some(value) if !value.is_null() => Ok(value.to_string()),
+        _ => Err(format!("{} has no key {}", path, key)),
+    }
+}
+
+/// Key-value pairs of a secret, nested in a second `data` field by the version 2 of the engine
+fn secret_data(document: &Value) -> Option<&Value> {
+    let data = document.get("data")?;
+    match (data.get("data"), data.get("metadata")) {
+        (Some(inner), Some(_)) => Some(inner),
+        _ => Some(data),
+    }
+}
+
+/// Message of an error response, a list of `errors`
+fn error_from_document(document: &Value) -> String {
+    let errors: Vec<&str> = match document.get("errors") {
+        Some(Value::Array(errors)) => errors.iter().filter_map(|e| e.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    if errors.is_empty() {
+        "unexpected response".to_string()
+    } else {
+        errors.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve a single response on a local port, returning its address
+    fn stub_server(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0; 4096];
+                let size = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..size]).to_lowercase();
+                let status = if request.contains("x-vault-token: s.1234") {
+                    status
+                } else {
+                    "403 Forbidden"
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn should_read_secrets_of_the_kv_engine() {
+        let body = "{\"data\":{\"data\":{\"STRIPE_KEY\":\"sk_live_1234\",\"RETRIES\":3},\"metadata\":{\"version\":2}}}";
+        let address = stub_server("200 OK", body);
+        assert_eq!(
+            read_from(&address, "s.1234", "secret/data/my-app#STRIPE_KEY"),
+            Ok("sk_live_1234".to_string())
+        );
+
+        let address = stub_server("200 OK", body);
+        assert_eq!(
+            read_from(&address, "s.1234", "secret/data/my-app#RETRIES"),
+            Ok("3".to_string())
+        );
+
+        let address = stub_server("200 OK", "{\"data\":{\"STRIPE_KEY\":\"sk_v1\"}}");
+        assert_eq!(
+            read_from(&address, "s.1234", "kv/my-app#STRIPE_KEY"),
+            Ok("sk_v1".to_string())
+        );
+    }
+
+    #[test]
+    fn should_report_missing_secrets() {
+        let address = stub_server("200 OK", "{\"data\":{\"data\":{},\"metadata\":{}}}");
+        assert_eq!(
+            read_from(&address, "s.1234", "secret/data/my-app#STRIPE_KEY"),
+            Err("secret/data/my-app has no key STRIPE_KEY".to_string())
+        );
+
+        let address = stub_server("200 OK", "{\"errors\":[\"permission denied\"]}");
+        assert_eq!(
+            read_from(&address, "s.4321", "secret/data/my-app#STRIPE_KEY"),
+            Err("secret/data/my-app 403 Forbidden: permission denied".to_string())
+        );
+
+        assert_eq!(
+            read_from("http://127.0.0.1:1", "s.1234", "secret/data/my-app"),
+            Err(
+                "invalid reference vault://secret/data/my-app, expected vault://PATH#KEY"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn should_not_save_the_secrets_read_from_vault() {
+        let body = "{\"data\":{\"data\":{\"STRIPE_KEY\":\"sk_live_1234\"},\"metadata\":{}}}";
+        env::set_var("VAULT_ADDR", stub_server("200 OK", body));
+        env::set_var("VAULT_TOKEN", "s.1234");
+        let path = "tests/vault_saved_test.yml";
+        let yaml = "version: \"2\"\ndefaults:\n  STRIPE_KEY: \"vault://secret/data/shared#STRIPE_KEY\"\napps:\n  - name: \"my-app\"\n    settings: {}\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.resolve_sources(path).unwrap();
+        assert_eq!(config.resolved_defaults["STRIPE_KEY"], "sk_live_1234");
+
+        config.save(path, true).unwrap();
+        let saved = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(saved.contains("STRIPE_KEY: \"vault://secret/data/shared#STRIPE_KEY\""));
+        assert!(!saved.contains("sk_live_1234"));
+
+        config.apply_defaults();
+        assert_eq!(config.apps[0].settings["STRIPE_KEY"], "sk_live_1234");
+    }
+}
@@ -0,0 +1,138 @@
+//! Watch a config file, to push its changes every time it is saved
+//!
+//! The file is polled for a change of its modification time, which also works when editors
+//! replace the file rather than writing into it.
+//!
+//! # Example
+//!
+//! ```rust
+//! let mut watcher = watch::Watcher::new("config.yml");
+//! loop {
+//!     watcher.wait_for_change();
+//!     let changes = watch::delta(&last_changes, &app.changes());
+//! }
+//! ```
+use std::collections::BTreeMap;
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Delay between two checks of the modification time of the file
+static POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct Watcher {
+    /// The path of the watched file
+    path: String,
+    /// Modification time of the file when it was last read
+    modified: Option<SystemTime>,
+}
+
+impl Watcher {
+    /// Start watching a file from its current state
+    ///
+    /// # Arguments
+    /// * `path` - A string containing the path to the file
+    ///
+    pub fn new(path: &str) -> Self {
+        Watcher {
+            path: path.to_string(),
+            modified: modified(path),
+        }
+    }
+
+    /// Block until the file is saved again
+    pub fn wait_for_change(&mut self) {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = modified(&self.path);
+            // A missing file is being replaced, it is read once it exists again
+            if current.is_some() && current != self.modified {
+                self.modified = current;
+                return;
+            }
+        }
+    }
+}
+
+/// Modification time of a file, if it exists
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Select the changes of an app that were not applied yet
+///
+/// A config var dropped from the config file is left as it is on heroku, like `push` does.
+///
+/// # Arguments
+/// * `applied` - The changes last applied to the app (see `App::changes`)
+/// * `changes` - The changes of the app in the config file
+///
+/// # Result
+/// * `BTreeMap<String, Option<String>>` - The new value of each config var changed since the last
+///   push, `None` to remove it
+///
+pub fn delta(
+    applied: &BTreeMap<String, Option<String>>,
+    changes: &BTreeMap<String, Option<String>>,
+) -> BTreeMap<String, Option<String>> {
+    changes
+        .iter()
+        .filter(|(key, value)| applied.get(*key) != Some(value))
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(pairs: &[(&str, Option<&str>)]) -> BTreeMap<String, Option<String>> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.map(|v| v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn should_select_the_changes_not_applied() {
+        let applied = changes(&[
+            ("ENV", Some("staging")),
+            ("PORT", Some("5000")),
+            ("OLD", None),
+        ]);
+        let current = changes(&[
+            ("ENV", Some("production")),
+            ("PORT", Some("5000")),
+            ("OLD", None),
+            ("NEW", Some("1")),
+            ("GONE", None),
+        ]);
+        assert_eq!(
+            delta(&applied, &current),
+            changes(&[
+                ("ENV", Some("production")),
+                ("GONE", None),
+                ("NEW", Some("1")),
+            ])
+        );
+        assert!(delta(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn should_detect_saved_files() {
+        let path = "tests/watch_test.yml";
+        fs::write(path, "version: \"2\"\n").unwrap();
+        let mut watcher = Watcher::new(path);
+        let before = watcher.modified;
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(1100));
+            fs::write(path, "version: \"2\"\napps: []\n").unwrap();
+        });
+        watcher.wait_for_change();
+        writer.join().unwrap();
+        fs::remove_file(path).unwrap();
+        assert_ne!(watcher.modified, before);
+    }
+}
@@ -0,0 +1,233 @@
+//! Parse a YAML document into a tree of nodes remembering their position in the file
+//!
+//! serde_yaml only gives the position of the first error it meets, this tree is used to report
+//! every problem of a config file with its line and column.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let root = yaml::parse("version: \"1\"\napps: []\n").unwrap().unwrap();
+//! let apps = root.get("apps").unwrap();
+//! assert_eq!(apps.position().line, 2);
+//! ```
+use std::fmt;
+
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// Position of a node in a YAML document, both line and column start at 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    /// Line of the node
+    pub line: usize,
+    /// Column of the node
+    pub column: usize,
+}
+
+impl Position {
+    fn from_marker(marker: &Marker) -> Self {
+        Position {
+            line: marker.line(),
+            column: marker.col() + 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum Node {
+    /// A scalar value (string, number, boolean, null)
+    Scalar(String, Position),
+    /// A list of nodes
+    Sequence(Vec<Node>, Position),
+    /// A list of key-value pairs, in the order of the document
+    Mapping(Vec<(Node, Node)>, Position),
+}
+
+impl Node {
+    /// Position of the start of the node in the document
+    pub fn position(&self) -> Position {
+        match *self {
+            Node::Scalar(_, position) => position,
+            Node::Sequence(_, position) => position,
+            Node::Mapping(_, position) => position,
+        }
+    }
+
+    /// Value of a scalar node
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Node::Scalar(ref value, _) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Check if the node is a null scalar (`~`, `null` or an empty value)
+    pub fn is_null(&self) -> bool {
+        match self.as_str() {
+            Some(value) => value == "~" || value == "null" || value.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Look up the value of a key of a mapping node
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match *self {
+            Node::Mapping(ref pairs, _) => pairs
+                .iter()
+                .find(|pair| pair.0.as_str() == Some(key))
+                .map(|pair| &pair.1),
+            _ => None,
+        }
+    }
+
+    /// Human description of the kind of node, used in error messages
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Node::Scalar(..) => "a value",
+            Node::Sequence(..) => "a list",
+            Node::Mapping(..) => "a map",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    /// Description of the syntax error
+    pub message: String,
+    /// Where the syntax error was found
+    pub position: Position,
+}
+
+/// Builds the tree of nodes from the events of the YAML parser
+struct Builder {
+    /// Collections being built, with the pending key of mappings
+    stack: Vec<(Node, Option<Node>)>,
+    /// The root node of the first document
+    root: Option<Node>,
+}
+
+impl Builder {
+    fn insert(&mut self, node: Node) {
+        match self.stack.last_mut() {
+            Some(&mut (Node::Sequence(ref mut nodes, _), _)) => nodes.push(node),
+            Some(&mut (Node::Mapping(ref mut pairs, _), ref mut key)) => match key.take() {
+                Some(k) => pairs.push((k, node)),
+                None => *key = Some(node),
+            },
+            Some(_) => {}
+            None => {
+                if self.root.is_none() {
+                    self.root = Some(node);
+                }
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for Builder {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        let position = Position::from_marker(&marker);
+        match event {
+            Event::Scalar(value, ..) => self.insert(Node::Scalar(value, position)),
+            Event::Alias(_) => self.insert(Node::Scalar(String::new(), position)),
+            Event::SequenceStart(_) => self
+                .stack
+                .push((Node::Sequence(Vec::new(), position), None)),
+            Event::MappingStart(_) => self.stack.push((Node::Mapping(Vec::new(), position), None)),
+            Event::SequenceEnd => {
+                if let Some((node, _)) = self.stack.pop() {
+                    self.insert(node);
+                }
+            }
+            Event::MappingEnd => {
+                // The parser marks the start of block mappings after their first key
+                if let Some((Node::Mapping(pairs, position), _)) = self.stack.pop() {
+                    let start = pairs.first().map_or(position, |pair| pair.0.position());
+                    self.insert(Node::Mapping(pairs, start));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse the first document of a YAML string
+///
+/// # Arguments
+/// * `yaml` - The content of a YAML file
+///
+/// # Result
+/// * `Result<Option<Node>, ParseError>` - The root node, None for an empty document, or the
+///   syntax error
+///
+pub fn parse(yaml: &str) -> Result<Option<Node>, ParseError> {
+    let mut builder = Builder {
+        stack: Vec::new(),
+        root: None,
+    };
+    let mut parser = Parser::new(yaml.chars());
+    match parser.load(&mut builder, false) {
+        Ok(_) => Ok(builder.root),
+        Err(err) => {
+            // The message of a ScanError ends with its position, it is reported separately
+            let message = err.to_string();
+            let message = message.split(" at line ").next().unwrap_or("").to_string();
+            Err(ParseError {
+                message,
+                position: Position::from_marker(err.marker()),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_with_positions() {
+        let root = parse("version: \"1\"\napps:\n  - name: \"my-app\"\n")
+            .unwrap()
+            .unwrap();
+        let version = root.get("version").unwrap();
+        assert_eq!(version.as_str(), Some("1"));
+        assert_eq!(
+            version.position(),
+            Position {
+                line: 1,
+                column: 10
+            }
+        );
+
+        let name = match *root.get("apps").unwrap() {
+            Node::Sequence(ref apps, _) => apps[0].get("name").unwrap(),
+            _ => panic!("apps should be a list"),
+        };
+        assert_eq!(name.as_str(), Some("my-app"));
+        assert_eq!(
+            name.position(),
+            Position {
+                line: 3,
+                column: 11
+            }
+        );
+    }
+
+    #[test]
+    fn should_parse_empty_document() {
+        assert!(parse("").unwrap().is_none());
+    }
+
+    #[test]
+    fn should_report_syntax_errors() {
+        let err = parse("apps:\n  - name: \"my-app\n").unwrap_err();
+        assert_eq!(err.position.line, 2);
+        assert!(!err.message.is_empty());
+    }
+}